@@ -1,69 +1,362 @@
-use crate::token::Token;
+use std::{fmt, rc::Rc};
+
+use crate::{span::Span, token::TokenKind};
 
 #[derive(PartialEq, Debug)]
 pub enum Statement {
-    Let(Expresion, Expresion),
-    Return(Expresion),
-    Expression(Expresion),
+    Let {
+        name: Expresion,
+        value: Expresion,
+        /// Text of the `///` comments directly above the binding.
+        doc: Option<String>,
+        span: Span,
+    },
+    Return(Expresion, Span),
+    Expression(Expresion, Span),
+    While {
+        condition: Expresion,
+        body: BlockStatement,
+        span: Span,
+    },
+    For {
+        variable: Expresion,
+        iterable: Expresion,
+        body: BlockStatement,
+        span: Span,
+    },
+    Break(Span),
+    Continue(Span),
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Let { span, .. }
+            | Statement::Return(_, span)
+            | Statement::Expression(_, span)
+            | Statement::While { span, .. }
+            | Statement::For { span, .. }
+            | Statement::Break(span)
+            | Statement::Continue(span) => return *span,
+        }
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Statement::Let {
+                name, value, doc, ..
+            } => {
+                for line in doc.iter().flat_map(|doc| doc.lines()) {
+                    writeln!(f, "/// {}", line)?;
+                }
+
+                write!(f, "let {} = {};", name, value)
+            }
+            Statement::Return(value, _) => write!(f, "return {};", value),
+            Statement::Expression(expression, _) => write!(f, "{}", expression),
+            Statement::While {
+                condition, body, ..
+            } => write!(f, "while {} {}", condition, body),
+            Statement::For {
+                variable,
+                iterable,
+                body,
+                ..
+            } => write!(f, "for {} in {} {}", variable, iterable, body),
+            Statement::Break(_) => write!(f, "break;"),
+            Statement::Continue(_) => write!(f, "continue;"),
+        }
+    }
 }
 
 #[derive(PartialEq, Debug)]
-pub enum Expresion {
-    Identifer(String),
-    Interger(u64),
-    Prefix(Operator, Box<Expresion>),
-    Infix(Box<Expresion>, Operator, Box<Expresion>),
-    Bool { value: bool },
+pub struct BlockStatement {
+    pub statements: Vec<Statement>,
+    pub span: Span,
+}
+
+impl fmt::Display for BlockStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{ ")?;
+
+        for statement in &self.statements {
+            write!(f, "{} ", statement)?;
+        }
+
+        write!(f, "}}")
+    }
 }
 
 #[derive(PartialEq, Debug)]
+pub enum Expresion {
+    Identifer(String, Span),
+    Interger(i64, Span),
+    String(String, Span),
+    Prefix(Operator, Box<Expresion>, Span),
+    Infix(Box<Expresion>, Operator, Box<Expresion>, Span),
+    Bool {
+        value: bool,
+        span: Span,
+    },
+    If {
+        condition: Box<Expresion>,
+        consequence: BlockStatement,
+        /// `else if` chains are an `alternative` holding a single `If`.
+        alternative: Option<BlockStatement>,
+        span: Span,
+    },
+    Function {
+        /// Parameter names, always `Identifer` nodes.
+        params: Vec<Expresion>,
+        /// Shared with every function value created from this literal.
+        body: Rc<BlockStatement>,
+        span: Span,
+    },
+    Call {
+        function: Box<Expresion>,
+        args: Vec<Expresion>,
+        span: Span,
+    },
+    Array(Vec<Expresion>, Span),
+    /// Key and value pairs in source order.
+    Hash(Vec<(Expresion, Expresion)>, Span),
+    Index {
+        left: Box<Expresion>,
+        index: Box<Expresion>,
+        span: Span,
+    },
+    /// `target = value`, or `target += value` and friends when `operator`
+    /// is set. The parser only allows variables and index expressions on
+    /// the left.
+    Assign {
+        target: Box<Expresion>,
+        operator: Option<Operator>,
+        value: Box<Expresion>,
+        span: Span,
+    },
+}
+
+impl Expresion {
+    pub fn span(&self) -> Span {
+        match self {
+            Expresion::Identifer(_, span)
+            | Expresion::Interger(_, span)
+            | Expresion::String(_, span)
+            | Expresion::Prefix(_, _, span)
+            | Expresion::Infix(_, _, _, span)
+            | Expresion::Bool { span, .. }
+            | Expresion::If { span, .. }
+            | Expresion::Function { span, .. }
+            | Expresion::Call { span, .. }
+            | Expresion::Array(_, span)
+            | Expresion::Hash(_, span)
+            | Expresion::Index { span, .. }
+            | Expresion::Assign { span, .. } => return *span,
+        }
+    }
+
+    pub fn span_mut(&mut self) -> &mut Span {
+        match self {
+            Expresion::Identifer(_, span)
+            | Expresion::Interger(_, span)
+            | Expresion::String(_, span)
+            | Expresion::Prefix(_, _, span)
+            | Expresion::Infix(_, _, _, span)
+            | Expresion::Bool { span, .. }
+            | Expresion::If { span, .. }
+            | Expresion::Function { span, .. }
+            | Expresion::Call { span, .. }
+            | Expresion::Array(_, span)
+            | Expresion::Hash(_, span)
+            | Expresion::Index { span, .. }
+            | Expresion::Assign { span, .. } => return span,
+        }
+    }
+}
+
+impl fmt::Display for Expresion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expresion::Identifer(name, _) => write!(f, "{}", name),
+            Expresion::Interger(value, _) => write!(f, "{}", value),
+            Expresion::String(value, _) => write!(f, "{:?}", value),
+            Expresion::Prefix(operator, right, _) => write!(f, "({}{})", operator, right),
+            Expresion::Infix(left, operator, right, _) => {
+                write!(f, "({} {} {})", left, operator, right)
+            }
+            Expresion::Bool { value, .. } => write!(f, "{}", value),
+            Expresion::If {
+                condition,
+                consequence,
+                alternative,
+                ..
+            } => {
+                write!(f, "if {} {}", condition, consequence)?;
+
+                if let Some(alternative) = alternative {
+                    write!(f, " else {}", alternative)?;
+                }
+
+                Ok(())
+            }
+            Expresion::Function { params, body, .. } => {
+                write!(f, "fn(")?;
+                write_list(f, params)?;
+                write!(f, ") {}", body)
+            }
+            Expresion::Call { function, args, .. } => {
+                write!(f, "{}(", function)?;
+                write_list(f, args)?;
+                write!(f, ")")
+            }
+            Expresion::Array(elements, _) => {
+                write!(f, "[")?;
+                write_list(f, elements)?;
+                write!(f, "]")
+            }
+            Expresion::Hash(pairs, _) => {
+                write!(f, "{{")?;
+
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}: {}", key, value)?;
+                }
+
+                write!(f, "}}")
+            }
+            Expresion::Index { left, index, .. } => write!(f, "({}[{}])", left, index),
+            Expresion::Assign {
+                target,
+                operator: Some(operator),
+                value,
+                ..
+            } => write!(f, "{} {}= {}", target, operator, value),
+            Expresion::Assign { target, value, .. } => write!(f, "{} = {}", target, value),
+        }
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter<'_>, expressions: &[Expresion]) -> fmt::Result {
+    for (i, expression) in expressions.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+
+        write!(f, "{}", expression)?;
+    }
+
+    Ok(())
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum Operator {
     Minus,
     Plus,
     Multiply,
     Divide,
+    Modulo,
+    Power,
     Gt,
     Lt,
+    GtEq,
+    LtEq,
     Equals,
     NotEquals,
     Not,
+    And,
+    Or,
 }
 
-impl From<Token> for Operator {
-    fn from(value: Token) -> Self {
+impl From<&TokenKind> for Operator {
+    fn from(value: &TokenKind) -> Self {
         match value {
-            Token::MINUS => return Operator::Minus,
-            Token::PLUS => return Operator::Plus,
-            Token::ASTERISK => return Operator::Multiply,
-            Token::SLASH => return Operator::Divide,
-            Token::GT => return Operator::Gt,
-            Token::LT => return Operator::Lt,
-            Token::EQ => return Operator::Equals,
-            Token::NOTEQ => return Operator::NotEquals,
-            Token::BANG => return Operator::Not,
+            TokenKind::MINUS | TokenKind::MINUSASSIGN => return Operator::Minus,
+            TokenKind::PLUS | TokenKind::PLUSASSIGN => return Operator::Plus,
+            TokenKind::ASTERISK | TokenKind::ASTERISKASSIGN => return Operator::Multiply,
+            TokenKind::SLASH | TokenKind::SLASHASSIGN => return Operator::Divide,
+            TokenKind::PERCENT => return Operator::Modulo,
+            TokenKind::POWER => return Operator::Power,
+            TokenKind::GT => return Operator::Gt,
+            TokenKind::LT => return Operator::Lt,
+            TokenKind::GTEQ => return Operator::GtEq,
+            TokenKind::LTEQ => return Operator::LtEq,
+            TokenKind::EQ => return Operator::Equals,
+            TokenKind::NOTEQ => return Operator::NotEquals,
+            TokenKind::BANG => return Operator::Not,
+            TokenKind::AND => return Operator::And,
+            TokenKind::OR => return Operator::Or,
             _ => unimplemented!("Token not valid to parse into operator {:?}", value),
         }
     }
 }
 
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Operator::Minus => "-",
+            Operator::Plus => "+",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::Modulo => "%",
+            Operator::Power => "**",
+            Operator::Gt => ">",
+            Operator::Lt => "<",
+            Operator::GtEq => ">=",
+            Operator::LtEq => "<=",
+            Operator::Equals => "==",
+            Operator::NotEquals => "!=",
+            Operator::Not => "!",
+            Operator::And => "&&",
+            Operator::Or => "||",
+        };
+
+        write!(f, "{}", symbol)
+    }
+}
+
 #[derive(PartialEq, PartialOrd)]
 pub enum Precedence {
     Lowest = 1,
-    Equals = 2,
-    Lessgreater = 3,
-    Sum = 4,
-    Product = 5,
-    Prefix = 6,
-    Call = 7,
+    Assign = 2,
+    Or = 3,
+    And = 4,
+    Equals = 5,
+    Lessgreater = 6,
+    Sum = 7,
+    Product = 8,
+    Prefix = 9,
+    /// Above `Prefix` so `-2 ** 2` is `-(2 ** 2)`.
+    Power = 10,
+    Call = 11,
+    Index = 12,
 }
 
-impl From<Token> for Precedence {
-    fn from(value: Token) -> Self {
+impl From<&TokenKind> for Precedence {
+    fn from(value: &TokenKind) -> Self {
         match value {
-            Token::LT | Token::GT => return Precedence::Lessgreater,
-            Token::EQ | Token::NOTEQ => return Precedence::Equals,
-            Token::PLUS | Token::MINUS => return Precedence::Sum,
-            Token::SLASH | Token::ASTERISK => return Precedence::Product,
+            TokenKind::ASSIGN
+            | TokenKind::PLUSASSIGN
+            | TokenKind::MINUSASSIGN
+            | TokenKind::ASTERISKASSIGN
+            | TokenKind::SLASHASSIGN => return Precedence::Assign,
+            TokenKind::OR => return Precedence::Or,
+            TokenKind::AND => return Precedence::And,
+            TokenKind::LT | TokenKind::GT | TokenKind::LTEQ | TokenKind::GTEQ => {
+                return Precedence::Lessgreater
+            }
+            TokenKind::EQ | TokenKind::NOTEQ => return Precedence::Equals,
+            TokenKind::PLUS | TokenKind::MINUS => return Precedence::Sum,
+            TokenKind::SLASH | TokenKind::ASTERISK | TokenKind::PERCENT => {
+                return Precedence::Product
+            }
+            TokenKind::POWER => return Precedence::Power,
+            TokenKind::LPAREN => return Precedence::Call,
+            TokenKind::LBRACKET => return Precedence::Index,
             _ => Precedence::Lowest,
         }
     }
@@ -72,3 +365,13 @@ impl From<Token> for Precedence {
 pub struct Program {
     pub statements: Vec<Statement>,
 }
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for statement in &self.statements {
+            writeln!(f, "{}", statement)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+//! Functions every script can call without defining them. Identifiers are
+//! looked up here only after the environment (or symbol table) came up
+//! empty, so scripts can shadow a builtin with their own `let`.
+
+use std::{io::Write, rc::Rc};
+
+use crate::{
+    convert::FromArgs,
+    evaluator::RuntimeError,
+    interpreter::Error,
+    object::{HostFunction, Object, Value},
+};
+
+type Builtin = fn(&[Value]) -> Result<Value, Error>;
+
+/// Indexed by `OpGetBuiltin`, so only ever append to this list.
+const BUILTINS: &[(&str, Builtin)] = &[
+    ("len", len),
+    ("puts", puts),
+    ("first", first),
+    ("last", last),
+    ("rest", rest),
+    ("push", push),
+    ("type_of", type_of),
+];
+
+thread_local! {
+    static OBJECTS: Vec<Object> = BUILTINS
+        .iter()
+        .map(|(name, func)| {
+            Object::Host(Rc::new(HostFunction {
+                name: String::from(*name),
+                func: Box::new(*func),
+            }))
+        })
+        .collect();
+}
+
+pub fn index_of(name: &str) -> Option<usize> {
+    return BUILTINS.iter().position(|(builtin, _)| *builtin == name);
+}
+
+/// The builtin at `index`, panics if there is none.
+pub fn get(index: usize) -> Object {
+    return OBJECTS.with(|objects| objects[index].clone());
+}
+
+pub fn lookup(name: &str) -> Option<Object> {
+    return index_of(name).map(get);
+}
+
+fn unsupported(function: &'static str, value: &Value) -> Error {
+    return Error::Runtime(RuntimeError::UnsupportedArgument {
+        function,
+        got: value.type_name(),
+    });
+}
+
+fn len(args: &[Value]) -> Result<Value, Error> {
+    let (value,) = <(Value,)>::from_args(args)?;
+
+    match &value {
+        Value::String(value) => return Ok(Value::Integer(value.chars().count() as i64)),
+        Value::Array(elements) => return Ok(Value::Integer(elements.len() as i64)),
+        Value::Hash(pairs) => return Ok(Value::Integer(pairs.len() as i64)),
+        _ => return Err(unsupported("len", &value)),
+    }
+}
+
+/// Prints each argument on its own line.
+fn puts(args: &[Value]) -> Result<Value, Error> {
+    return puts_to(&mut std::io::stdout().lock(), args);
+}
+
+/// `puts` writing to `output` instead of stdout.
+pub fn puts_to(output: &mut dyn Write, args: &[Value]) -> Result<Value, Error> {
+    for arg in args {
+        if let Err(error) = writeln!(output, "{}", arg) {
+            return Err(Error::custom(format!("puts failed: {}", error)));
+        }
+    }
+
+    return Ok(Value::Null);
+}
+
+fn first(args: &[Value]) -> Result<Value, Error> {
+    let (array,) = <(Value,)>::from_args(args)?;
+
+    match &array {
+        Value::Array(elements) => return Ok(elements.first().cloned().unwrap_or(Value::Null)),
+        _ => return Err(unsupported("first", &array)),
+    }
+}
+
+fn last(args: &[Value]) -> Result<Value, Error> {
+    let (array,) = <(Value,)>::from_args(args)?;
+
+    match &array {
+        Value::Array(elements) => return Ok(elements.last().cloned().unwrap_or(Value::Null)),
+        _ => return Err(unsupported("last", &array)),
+    }
+}
+
+/// Everything but the first element, or null for an empty array.
+fn rest(args: &[Value]) -> Result<Value, Error> {
+    let (array,) = <(Value,)>::from_args(args)?;
+
+    match &array {
+        Value::Array(elements) if elements.is_empty() => return Ok(Value::Null),
+        Value::Array(elements) => return Ok(Value::Array(Rc::new(elements[1..].to_vec()))),
+        _ => return Err(unsupported("rest", &array)),
+    }
+}
+
+/// A new array with `value` appended, the original is left alone.
+fn push(args: &[Value]) -> Result<Value, Error> {
+    let (array, value) = <(Value, Value)>::from_args(args)?;
+
+    match &array {
+        Value::Array(elements) => {
+            let mut elements = elements.to_vec();
+            elements.push(value);
+
+            return Ok(Value::Array(Rc::new(elements)));
+        }
+        _ => return Err(unsupported("push", &array)),
+    }
+}
+
+fn type_of(args: &[Value]) -> Result<Value, Error> {
+    let (value,) = <(Value,)>::from_args(args)?;
+
+    return Ok(Value::String(String::from(value.type_name())));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lookup;
+    use crate::{
+        convert::IntoValue,
+        evaluator::RuntimeError,
+        interpreter::Error,
+        object::{Object, Value},
+    };
+
+    fn call(name: &str, args: Vec<Value>) -> Result<Value, Error> {
+        let Some(Object::Host(builtin)) = lookup(name) else {
+            panic!("Expected builtin {}", name);
+        };
+
+        return (builtin.func)(&args);
+    }
+
+    #[test]
+    fn test_array_builtins() {
+        let array = vec![1, 2, 3].into_value();
+        let empty = Vec::<i64>::new().into_value();
+
+        assert_eq!(call("len", vec![array.clone()]), Ok(Value::Integer(3)));
+        assert_eq!(call("first", vec![array.clone()]), Ok(Value::Integer(1)));
+        assert_eq!(call("last", vec![array.clone()]), Ok(Value::Integer(3)));
+        assert_eq!(
+            call("rest", vec![array.clone()]),
+            Ok(vec![2, 3].into_value())
+        );
+        assert_eq!(
+            call("push", vec![array.clone(), Value::Integer(4)]),
+            Ok(vec![1, 2, 3, 4].into_value())
+        );
+
+        assert_eq!(call("first", vec![empty.clone()]), Ok(Value::Null));
+        assert_eq!(call("last", vec![empty.clone()]), Ok(Value::Null));
+        assert_eq!(call("rest", vec![empty.clone()]), Ok(Value::Null));
+        assert_eq!(
+            call("push", vec![empty, Value::Null]),
+            Ok(vec![Value::Null].into_value())
+        );
+    }
+
+    #[test]
+    fn test_builtin_errors() {
+        assert_eq!(
+            call("first", vec![Value::Integer(1)]),
+            Err(Error::Runtime(RuntimeError::UnsupportedArgument {
+                function: "first",
+                got: "INTEGER",
+            }))
+        );
+        assert_eq!(
+            call("push", vec![Value::Integer(1)]),
+            Err(Error::Runtime(RuntimeError::WrongArgumentCount {
+                expected: 2,
+                got: 1,
+            }))
+        );
+    }
+}
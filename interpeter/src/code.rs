@@ -0,0 +1,292 @@
+//! The bytecode format shared by the compiler and the VM. An instruction is
+//! a one byte opcode followed by its operands, each stored big-endian in
+//! the width given by [`Opcode::operand_widths`].
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum Opcode {
+    Constant,
+    Pop,
+    True,
+    False,
+    Null,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Equal,
+    NotEqual,
+    GreaterThan,
+    LessThan,
+    GreaterEqual,
+    LessEqual,
+    Minus,
+    Bang,
+    Jump,
+    JumpNotTruthy,
+    GetGlobal,
+    SetGlobal,
+    GetLocal,
+    SetLocal,
+    Call,
+    ReturnValue,
+    Closure,
+    GetUpvalue,
+    SetUpvalue,
+    GetBuiltin,
+    Array,
+    Hash,
+    Index,
+    EnterLoop,
+    ExitLoop,
+    UnwindLoop,
+    IterInit,
+    IterNext,
+    SetIndex,
+    CheckGlobal,
+}
+
+/// Every opcode, indexed by its byte value.
+const OPCODES: [Opcode; 41] = [
+    Opcode::Constant,
+    Opcode::Pop,
+    Opcode::True,
+    Opcode::False,
+    Opcode::Null,
+    Opcode::Add,
+    Opcode::Sub,
+    Opcode::Mul,
+    Opcode::Div,
+    Opcode::Mod,
+    Opcode::Pow,
+    Opcode::Equal,
+    Opcode::NotEqual,
+    Opcode::GreaterThan,
+    Opcode::LessThan,
+    Opcode::GreaterEqual,
+    Opcode::LessEqual,
+    Opcode::Minus,
+    Opcode::Bang,
+    Opcode::Jump,
+    Opcode::JumpNotTruthy,
+    Opcode::GetGlobal,
+    Opcode::SetGlobal,
+    Opcode::GetLocal,
+    Opcode::SetLocal,
+    Opcode::Call,
+    Opcode::ReturnValue,
+    Opcode::Closure,
+    Opcode::GetUpvalue,
+    Opcode::SetUpvalue,
+    Opcode::GetBuiltin,
+    Opcode::Array,
+    Opcode::Hash,
+    Opcode::Index,
+    Opcode::EnterLoop,
+    Opcode::ExitLoop,
+    Opcode::UnwindLoop,
+    Opcode::IterInit,
+    Opcode::IterNext,
+    Opcode::SetIndex,
+    Opcode::CheckGlobal,
+];
+
+impl Opcode {
+    /// Width in bytes of each operand that follows the opcode.
+    pub fn operand_widths(self) -> &'static [usize] {
+        match self {
+            Opcode::Constant
+            | Opcode::Jump
+            | Opcode::JumpNotTruthy
+            | Opcode::GetGlobal
+            | Opcode::SetGlobal
+            | Opcode::CheckGlobal
+            | Opcode::Closure
+            | Opcode::Array
+            | Opcode::Hash
+            | Opcode::IterNext => return &[2],
+            Opcode::GetLocal
+            | Opcode::SetLocal
+            | Opcode::Call
+            | Opcode::GetUpvalue
+            | Opcode::SetUpvalue
+            | Opcode::GetBuiltin
+            | Opcode::EnterLoop
+            | Opcode::ExitLoop => return &[1],
+            Opcode::UnwindLoop | Opcode::SetIndex => return &[1, 1],
+            _ => return &[],
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Opcode::Constant => return "OpConstant",
+            Opcode::Pop => return "OpPop",
+            Opcode::True => return "OpTrue",
+            Opcode::False => return "OpFalse",
+            Opcode::Null => return "OpNull",
+            Opcode::Add => return "OpAdd",
+            Opcode::Sub => return "OpSub",
+            Opcode::Mul => return "OpMul",
+            Opcode::Div => return "OpDiv",
+            Opcode::Mod => return "OpMod",
+            Opcode::Pow => return "OpPow",
+            Opcode::Equal => return "OpEqual",
+            Opcode::NotEqual => return "OpNotEqual",
+            Opcode::GreaterThan => return "OpGreaterThan",
+            Opcode::LessThan => return "OpLessThan",
+            Opcode::GreaterEqual => return "OpGreaterEqual",
+            Opcode::LessEqual => return "OpLessEqual",
+            Opcode::Minus => return "OpMinus",
+            Opcode::Bang => return "OpBang",
+            Opcode::Jump => return "OpJump",
+            Opcode::JumpNotTruthy => return "OpJumpNotTruthy",
+            Opcode::GetGlobal => return "OpGetGlobal",
+            Opcode::SetGlobal => return "OpSetGlobal",
+            Opcode::GetLocal => return "OpGetLocal",
+            Opcode::SetLocal => return "OpSetLocal",
+            Opcode::Call => return "OpCall",
+            Opcode::ReturnValue => return "OpReturnValue",
+            Opcode::Closure => return "OpClosure",
+            Opcode::GetUpvalue => return "OpGetUpvalue",
+            Opcode::SetUpvalue => return "OpSetUpvalue",
+            Opcode::GetBuiltin => return "OpGetBuiltin",
+            Opcode::Array => return "OpArray",
+            Opcode::Hash => return "OpHash",
+            Opcode::Index => return "OpIndex",
+            Opcode::EnterLoop => return "OpEnterLoop",
+            Opcode::ExitLoop => return "OpExitLoop",
+            Opcode::UnwindLoop => return "OpUnwindLoop",
+            Opcode::IterInit => return "OpIterInit",
+            Opcode::IterNext => return "OpIterNext",
+            Opcode::SetIndex => return "OpSetIndex",
+            Opcode::CheckGlobal => return "OpCheckGlobal",
+        }
+    }
+
+    /// Size of the whole instruction, opcode included.
+    pub fn width(self) -> usize {
+        return 1 + self.operand_widths().iter().sum::<usize>();
+    }
+}
+
+impl TryFrom<u8> for Opcode {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        return OPCODES.get(value as usize).copied().ok_or(value);
+    }
+}
+
+/// Largest value an operand `width` bytes wide can hold.
+pub fn max_operand(width: usize) -> usize {
+    return (1 << (width * 8)) - 1;
+}
+
+/// Encodes one instruction. Panics if the operand count does not match
+/// the opcode or an operand does not fit its width, which is always a
+/// compiler bug.
+pub fn make(op: Opcode, operands: &[usize]) -> Vec<u8> {
+    let widths = op.operand_widths();
+    assert_eq!(widths.len(), operands.len(), "operands for {}", op.name());
+
+    let mut instruction = Vec::with_capacity(op.width());
+    instruction.push(op as u8);
+
+    for (operand, width) in operands.iter().zip(widths) {
+        assert!(
+            *operand <= max_operand(*width),
+            "operand {} too large for {}",
+            operand,
+            op.name()
+        );
+
+        match width {
+            1 => instruction.push(*operand as u8),
+            2 => instruction.extend_from_slice(&(*operand as u16).to_be_bytes()),
+            _ => unreachable!("Unsupported operand width {}", width),
+        }
+    }
+
+    return instruction;
+}
+
+/// Decodes the operands of `op` from the bytes right after the opcode.
+pub fn read_operands(op: Opcode, bytes: &[u8]) -> Vec<usize> {
+    let mut operands = Vec::with_capacity(op.operand_widths().len());
+    let mut offset = 0;
+
+    for width in op.operand_widths() {
+        match width {
+            1 => operands.push(bytes[offset] as usize),
+            2 => operands.push(read_u16(bytes, offset) as usize),
+            _ => unreachable!("Unsupported operand width {}", width),
+        }
+
+        offset += width;
+    }
+
+    return operands;
+}
+
+pub fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    return u16::from_be_bytes([bytes[offset], bytes[offset + 1]]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{make, read_operands, Opcode, OPCODES};
+
+    #[test]
+    fn test_make() {
+        let tests = vec![
+            (
+                Opcode::Constant,
+                vec![65534],
+                vec![Opcode::Constant as u8, 255, 254],
+            ),
+            (
+                Opcode::GetLocal,
+                vec![255],
+                vec![Opcode::GetLocal as u8, 255],
+            ),
+            (Opcode::Add, vec![], vec![Opcode::Add as u8]),
+        ];
+
+        for (op, operands, expected) in tests {
+            assert_eq!(make(op, &operands), expected, "{}", op.name());
+        }
+    }
+
+    #[test]
+    fn test_read_operands() {
+        let tests = vec![
+            (Opcode::Constant, vec![65535]),
+            (Opcode::Call, vec![3]),
+            (Opcode::UnwindLoop, vec![1, 2]),
+            (Opcode::Pop, vec![]),
+        ];
+
+        for (op, operands) in tests {
+            let instruction = make(op, &operands);
+
+            assert_eq!(instruction.len(), op.width());
+            assert_eq!(read_operands(op, &instruction[1..]), operands);
+        }
+    }
+
+    #[test]
+    fn test_opcode_round_trip() {
+        for (byte, op) in OPCODES.iter().enumerate() {
+            assert_eq!(*op as usize, byte, "{}", op.name());
+            assert_eq!(Opcode::try_from(byte as u8), Ok(*op));
+        }
+
+        assert_eq!(
+            Opcode::try_from(OPCODES.len() as u8),
+            Err(OPCODES.len() as u8)
+        );
+    }
+}
@@ -0,0 +1,1199 @@
+use std::{collections::HashSet, fmt, rc::Rc};
+
+use crate::{
+    ast::{BlockStatement, Expresion, Operator, Program, Statement},
+    builtins,
+    code::{make, max_operand, Opcode},
+    object::{CompiledFunction, Object},
+    symbol_table::{Symbol, SymbolScope, SymbolTable},
+};
+
+#[derive(PartialEq, Debug)]
+pub enum CompileError {
+    /// The program needs an operand larger than its instruction can hold,
+    /// like a function with more locals than `OpGetLocal` can address.
+    TooMany { what: &'static str, limit: usize },
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::TooMany { what, limit } => {
+                write!(f, "too many {}, the limit is {}", what, limit)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// Output of the compiler, everything the VM needs to run a program.
+#[derive(PartialEq, Debug)]
+pub struct Bytecode {
+    pub instructions: Vec<u8>,
+    /// Source line of each instruction as `(offset, line)` pairs.
+    pub lines: Vec<(usize, usize)>,
+    pub constants: Vec<Object>,
+    /// Name of each global slot, to report reads of one whose `let` has
+    /// not run yet.
+    pub global_names: Vec<String>,
+}
+
+/// Operand of a jump emitted before its target is known.
+const UNPATCHED: usize = u16::MAX as usize;
+
+#[derive(Clone, Copy)]
+struct EmittedInstruction {
+    opcode: Opcode,
+    position: usize,
+}
+
+/// A loop whose body is being compiled.
+struct Loop {
+    /// Where `continue` jumps to.
+    start: usize,
+    /// Values the loop keeps on the stack while its body runs.
+    kept: usize,
+    /// `break` jumps, patched once the end of the loop is known.
+    breaks: Vec<usize>,
+}
+
+/// Instructions of the function body currently being compiled.
+#[derive(Default)]
+struct CompilationScope {
+    instructions: Vec<u8>,
+    lines: Vec<(usize, usize)>,
+    last: Option<EmittedInstruction>,
+    previous: Option<EmittedInstruction>,
+    loops: Vec<Loop>,
+}
+
+/// Lowers a parsed program to bytecode for the [`Vm`](crate::vm::Vm).
+pub struct Compiler {
+    constants: Vec<Object>,
+    symbols: SymbolTable,
+    scopes: Vec<CompilationScope>,
+    /// Source line of the node being compiled.
+    line: usize,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        return Compiler::new();
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        return Compiler {
+            constants: Vec::new(),
+            symbols: SymbolTable::new(),
+            scopes: vec![CompilationScope::default()],
+            line: 1,
+        };
+    }
+
+    /// Binds `value` to the global `name` before the program runs, which
+    /// is how functions registered with the [`Interpreter`](crate::Interpreter)
+    /// reach the VM.
+    pub fn define_global(&mut self, name: &str, value: Object) -> Result<(), CompileError> {
+        let symbol = self.symbols.define(name);
+        let index = self.add_constant(value);
+
+        self.emit(Opcode::Constant, &[index])?;
+        self.store_symbol(&symbol)?;
+
+        return Ok(());
+    }
+
+    pub fn compile(mut self, program: &Program) -> Result<Bytecode, CompileError> {
+        for statement in &program.statements {
+            self.compile_statement(statement)?;
+        }
+
+        // The VM hands back the last popped value, make sure a program that
+        // ends in a `let` evaluates to null like it does in the evaluator.
+        if !matches!(program.statements.last(), Some(Statement::Expression(..))) {
+            self.emit(Opcode::Null, &[])?;
+            self.emit(Opcode::Pop, &[])?;
+        }
+
+        let scope = self.scopes.pop().expect("main scope is never left");
+
+        return Ok(Bytecode {
+            instructions: scope.instructions,
+            lines: scope.lines,
+            constants: self.constants,
+            global_names: self.symbols.names(),
+        });
+    }
+
+    fn compile_statement(&mut self, statement: &Statement) -> Result<(), CompileError> {
+        let outer_line = std::mem::replace(&mut self.line, statement.span().line);
+
+        match statement {
+            Statement::Let {
+                name: Expresion::Identifer(name, _),
+                value,
+                ..
+            } => {
+                // Declared before the value is compiled so functions in it
+                // can refer to themselves.
+                let symbol = self.symbols.declare(name);
+                self.compile_expression(value)?;
+                self.symbols.initialize(name);
+                self.store_symbol(&symbol)?;
+            }
+            Statement::Let { name, .. } => {
+                unreachable!(
+                    "Parser produced let statement without identifier {:?}",
+                    name
+                )
+            }
+            Statement::Return(value, _) => {
+                self.compile_expression(value)?;
+                self.emit(Opcode::ReturnValue, &[])?;
+            }
+            Statement::Expression(expression, _) => {
+                self.compile_expression(expression)?;
+                self.emit(Opcode::Pop, &[])?;
+            }
+            Statement::While {
+                condition, body, ..
+            } => {
+                let depth = self.scope().loops.len();
+                self.emit(Opcode::EnterLoop, &[depth])?;
+
+                let start = self.current_position();
+                self.compile_expression(condition)?;
+                let exit = self.emit(Opcode::JumpNotTruthy, &[UNPATCHED])?;
+
+                let breaks = self.compile_loop_body(body, start, 0)?;
+                self.finish_loop(depth, exit, breaks)?;
+            }
+            Statement::For {
+                variable: Expresion::Identifer(name, _),
+                iterable,
+                body,
+                ..
+            } => {
+                let depth = self.scope().loops.len();
+                self.emit(Opcode::EnterLoop, &[depth])?;
+
+                self.compile_expression(iterable)?;
+                self.emit(Opcode::IterInit, &[])?;
+
+                let symbol = self.symbols.define(name);
+                let start = self.current_position();
+                let exit = self.emit(Opcode::IterNext, &[UNPATCHED])?;
+                self.store_symbol(&symbol)?;
+
+                // The items and the position in them stay on the stack.
+                let breaks = self.compile_loop_body(body, start, 2)?;
+                self.finish_loop(depth, exit, breaks)?;
+            }
+            Statement::For { variable, .. } => {
+                unreachable!(
+                    "Parser produced for statement without identifier {:?}",
+                    variable
+                )
+            }
+            Statement::Break(_) => {
+                let jump = self.emit(Opcode::Jump, &[UNPATCHED])?;
+                self.innermost_loop().breaks.push(jump);
+            }
+            Statement::Continue(_) => {
+                let depth = self.scope().loops.len() - 1;
+                let Loop { start, kept, .. } = *self.innermost_loop();
+
+                self.emit(Opcode::UnwindLoop, &[depth, kept])?;
+                self.emit(Opcode::Jump, &[start])?;
+            }
+        }
+
+        self.line = outer_line;
+
+        return Ok(());
+    }
+
+    /// Ends a `&&` or `||` whose true result was just pushed with the
+    /// `false` the `jumps` lead to.
+    fn compile_false_branch(&mut self, jumps: &[usize]) -> Result<(), CompileError> {
+        let skip = self.emit(Opcode::Jump, &[UNPATCHED])?;
+        let position = self.current_position();
+
+        for jump in jumps {
+            self.change_operand(*jump, position)?;
+        }
+
+        self.emit(Opcode::False, &[])?;
+
+        let end = self.current_position();
+        self.change_operand(skip, end)?;
+
+        return Ok(());
+    }
+
+    /// Compiles the statements of a loop body followed by the jump back to
+    /// `start`. Returns the `break` jumps still to be patched.
+    fn compile_loop_body(
+        &mut self,
+        body: &BlockStatement,
+        start: usize,
+        kept: usize,
+    ) -> Result<Vec<usize>, CompileError> {
+        self.scope().loops.push(Loop {
+            start,
+            kept,
+            breaks: Vec::new(),
+        });
+
+        for statement in &body.statements {
+            self.compile_statement(statement)?;
+        }
+
+        self.emit(Opcode::Jump, &[start])?;
+
+        let Loop { breaks, .. } = self.scope().loops.pop().expect("pushed above");
+
+        return Ok(breaks);
+    }
+
+    /// Points `exit` and the `break`s at the end of the loop, where the
+    /// stack is cut back to what it was before the loop started.
+    fn finish_loop(
+        &mut self,
+        depth: usize,
+        exit: usize,
+        breaks: Vec<usize>,
+    ) -> Result<(), CompileError> {
+        let end = self.current_position();
+
+        for jump in std::iter::once(exit).chain(breaks) {
+            self.change_operand(jump, end)?;
+        }
+
+        self.emit(Opcode::ExitLoop, &[depth])?;
+
+        return Ok(());
+    }
+
+    fn innermost_loop(&mut self) -> &mut Loop {
+        return self
+            .scope()
+            .loops
+            .last_mut()
+            .expect("parser only allows break and continue inside loops");
+    }
+
+    /// Compiles a block so it leaves exactly one value on the stack, the
+    /// value of its last expression or null.
+    fn compile_block(&mut self, block: &BlockStatement) -> Result<(), CompileError> {
+        for statement in &block.statements {
+            self.compile_statement(statement)?;
+        }
+
+        if self.last_instruction_is(Opcode::Pop) {
+            self.remove_last_pop();
+        } else if !self.last_instruction_is(Opcode::ReturnValue) {
+            self.emit(Opcode::Null, &[])?;
+        }
+
+        return Ok(());
+    }
+
+    fn compile_expression(&mut self, expression: &Expresion) -> Result<(), CompileError> {
+        let outer_line = std::mem::replace(&mut self.line, expression.span().line);
+
+        match expression {
+            Expresion::Identifer(name, _) => self.compile_identifier(name)?,
+            Expresion::Interger(value, _) => {
+                let index = self.add_constant(Object::Integer(*value));
+                self.emit(Opcode::Constant, &[index])?;
+            }
+            Expresion::String(value, _) => {
+                let index = self.add_constant(Object::String(value.to_owned()));
+                self.emit(Opcode::Constant, &[index])?;
+            }
+            Expresion::Bool { value: true, .. } => {
+                self.emit(Opcode::True, &[])?;
+            }
+            Expresion::Bool { value: false, .. } => {
+                self.emit(Opcode::False, &[])?;
+            }
+            Expresion::Prefix(operator, right, _) => {
+                self.compile_expression(right)?;
+
+                match operator {
+                    Operator::Minus => self.emit(Opcode::Minus, &[])?,
+                    Operator::Not => self.emit(Opcode::Bang, &[])?,
+                    _ => unreachable!("Parser produced prefix operator {}", operator),
+                };
+            }
+            Expresion::Infix(left, Operator::And, right, _) => {
+                self.compile_expression(left)?;
+                let left_false = self.emit(Opcode::JumpNotTruthy, &[UNPATCHED])?;
+
+                self.compile_expression(right)?;
+                let right_false = self.emit(Opcode::JumpNotTruthy, &[UNPATCHED])?;
+
+                self.emit(Opcode::True, &[])?;
+                self.compile_false_branch(&[left_false, right_false])?;
+            }
+            Expresion::Infix(left, Operator::Or, right, _) => {
+                self.compile_expression(left)?;
+                let left_false = self.emit(Opcode::JumpNotTruthy, &[UNPATCHED])?;
+
+                self.emit(Opcode::True, &[])?;
+                let left_true = self.emit(Opcode::Jump, &[UNPATCHED])?;
+
+                let right_start = self.current_position();
+                self.change_operand(left_false, right_start)?;
+
+                self.compile_expression(right)?;
+                let right_false = self.emit(Opcode::JumpNotTruthy, &[UNPATCHED])?;
+
+                self.emit(Opcode::True, &[])?;
+                self.compile_false_branch(&[right_false])?;
+
+                let end = self.current_position();
+                self.change_operand(left_true, end)?;
+            }
+            Expresion::Infix(left, operator, right, _) => {
+                self.compile_expression(left)?;
+                self.compile_expression(right)?;
+                self.emit(infix_opcode(*operator), &[])?;
+            }
+            Expresion::If {
+                condition,
+                consequence,
+                alternative,
+                ..
+            } => {
+                self.compile_expression(condition)?;
+
+                // Jump targets are patched in once the blocks are compiled.
+                let jump_not_truthy = self.emit(Opcode::JumpNotTruthy, &[UNPATCHED])?;
+                self.compile_block(consequence)?;
+
+                let jump = self.emit(Opcode::Jump, &[UNPATCHED])?;
+                let after_consequence = self.current_position();
+                self.change_operand(jump_not_truthy, after_consequence)?;
+
+                match alternative {
+                    Some(alternative) => self.compile_block(alternative)?,
+                    None => {
+                        self.emit(Opcode::Null, &[])?;
+                    }
+                }
+
+                let after_alternative = self.current_position();
+                self.change_operand(jump, after_alternative)?;
+            }
+            Expresion::Function { params, body, .. } => {
+                self.enter_scope();
+                collect_bindings(body, &mut self.symbols.bound_later);
+
+                for param in params {
+                    self.symbols.define(&param.to_string());
+                }
+
+                self.compile_block(body)?;
+
+                if !self.last_instruction_is(Opcode::ReturnValue) {
+                    self.emit(Opcode::ReturnValue, &[])?;
+                }
+
+                let num_locals = self.symbols.num_definitions;
+                let captures = std::mem::take(&mut self.symbols.captures);
+                let scope = self.leave_scope();
+
+                let function = CompiledFunction {
+                    instructions: scope.instructions,
+                    lines: scope.lines,
+                    num_locals,
+                    num_params: params.len(),
+                    captures,
+                };
+
+                let index = self.add_constant(Object::CompiledFunction(Rc::new(function)));
+                self.emit(Opcode::Closure, &[index])?;
+            }
+            Expresion::Call { function, args, .. } => {
+                self.compile_expression(function)?;
+
+                for arg in args {
+                    self.compile_expression(arg)?;
+                }
+
+                self.emit(Opcode::Call, &[args.len()])?;
+            }
+            Expresion::Array(elements, _) => {
+                for element in elements {
+                    self.compile_expression(element)?;
+                }
+
+                self.emit(Opcode::Array, &[elements.len()])?;
+            }
+            Expresion::Hash(pairs, _) => {
+                for (key, value) in pairs {
+                    self.compile_expression(key)?;
+                    self.compile_expression(value)?;
+                }
+
+                self.emit(Opcode::Hash, &[pairs.len() * 2])?;
+            }
+            Expresion::Index { left, index, .. } => {
+                self.compile_expression(left)?;
+                self.compile_expression(index)?;
+                self.emit(Opcode::Index, &[])?;
+            }
+            Expresion::Assign {
+                target,
+                operator,
+                value,
+                ..
+            } => self.compile_assign(target, *operator, value)?,
+        }
+
+        self.line = outer_line;
+
+        return Ok(());
+    }
+
+    /// Loads a variable, falling back to the builtins for names that
+    /// were never bound. Any other name may still be a global defined
+    /// further down.
+    fn compile_identifier(&mut self, name: &str) -> Result<(), CompileError> {
+        let symbol = match self.symbols.resolve(name) {
+            Some(symbol) => symbol,
+            None => match builtins::index_of(name) {
+                Some(index) => {
+                    self.emit(Opcode::GetBuiltin, &[index])?;
+                    return Ok(());
+                }
+                None => self.symbols.define_forward(name),
+            },
+        };
+
+        self.load_symbol(&symbol)?;
+
+        return Ok(());
+    }
+
+    /// Leaves the assigned value on the stack. Index assignment loads the
+    /// indexes, the value and the variable, and `OpSetIndex` swaps them for
+    /// the value and the updated variable, which is then stored.
+    fn compile_assign(
+        &mut self,
+        target: &Expresion,
+        operator: Option<Operator>,
+        value: &Expresion,
+    ) -> Result<(), CompileError> {
+        let mut indexes = Vec::new();
+        let mut place = target;
+
+        while let Expresion::Index { left, index, .. } = place {
+            indexes.push(index.as_ref());
+            place = left;
+        }
+
+        let Expresion::Identifer(name, _) = place else {
+            unreachable!("Parser produced assignment to {:?}", place);
+        };
+
+        let symbol = match self.symbols.resolve(name) {
+            Some(symbol) => symbol,
+            None => self.symbols.define_forward(name),
+        };
+
+        // Like in the evaluator, assigning to a global fails only once it
+        // runs before any `let` has bound the name.
+        if symbol.scope == SymbolScope::Global {
+            self.emit(Opcode::CheckGlobal, &[symbol.index])?;
+        }
+
+        if indexes.is_empty() {
+            if let Some(operator) = operator {
+                self.load_symbol(&symbol)?;
+                self.compile_expression(value)?;
+                self.emit(infix_opcode(operator), &[])?;
+            } else {
+                self.compile_expression(value)?;
+            }
+
+            self.store_symbol(&symbol)?;
+            self.load_symbol(&symbol)?;
+
+            return Ok(());
+        }
+
+        for index in indexes.iter().rev() {
+            self.compile_expression(index)?;
+        }
+
+        self.compile_expression(value)?;
+
+        // The variable is cleared while its value is on the stack, so that
+        // copy is not shared and `OpSetIndex` can change it in place.
+        self.load_symbol(&symbol)?;
+        self.emit(Opcode::Null, &[])?;
+        self.store_symbol(&symbol)?;
+
+        // The arithmetic opcode of a compound assignment, `OpNull` for `=`.
+        let operator = operator.map_or(Opcode::Null, infix_opcode);
+        self.emit(Opcode::SetIndex, &[indexes.len(), operator as usize])?;
+        self.store_symbol(&symbol)?;
+
+        return Ok(());
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol) -> Result<(), CompileError> {
+        match symbol.scope {
+            SymbolScope::Global => self.emit(Opcode::GetGlobal, &[symbol.index])?,
+            SymbolScope::Local => self.emit(Opcode::GetLocal, &[symbol.index])?,
+            SymbolScope::Upvalue => self.emit(Opcode::GetUpvalue, &[symbol.index])?,
+        };
+
+        return Ok(());
+    }
+
+    fn store_symbol(&mut self, symbol: &Symbol) -> Result<(), CompileError> {
+        match symbol.scope {
+            SymbolScope::Global => self.emit(Opcode::SetGlobal, &[symbol.index])?,
+            SymbolScope::Local => self.emit(Opcode::SetLocal, &[symbol.index])?,
+            SymbolScope::Upvalue => self.emit(Opcode::SetUpvalue, &[symbol.index])?,
+        };
+
+        return Ok(());
+    }
+
+    fn add_constant(&mut self, object: Object) -> usize {
+        self.constants.push(object);
+        return self.constants.len() - 1;
+    }
+
+    /// Appends an instruction to the current scope and returns its position.
+    fn emit(&mut self, opcode: Opcode, operands: &[usize]) -> Result<usize, CompileError> {
+        check_operands(opcode, operands)?;
+
+        let instruction = make(opcode, operands);
+        let line = self.line;
+        let scope = self.scope();
+        let position = scope.instructions.len();
+
+        scope.instructions.extend(instruction);
+        scope.lines.push((position, line));
+        scope.previous = scope.last;
+        scope.last = Some(EmittedInstruction { opcode, position });
+
+        return Ok(position);
+    }
+
+    fn scope(&mut self) -> &mut CompilationScope {
+        return self.scopes.last_mut().expect("main scope is never left");
+    }
+
+    fn current_position(&mut self) -> usize {
+        return self.scope().instructions.len();
+    }
+
+    fn last_instruction_is(&mut self, opcode: Opcode) -> bool {
+        return matches!(self.scope().last, Some(last) if last.opcode == opcode);
+    }
+
+    fn remove_last_pop(&mut self) {
+        let scope = self.scope();
+        let last = scope.last.expect("there is a pop to remove");
+
+        scope.instructions.truncate(last.position);
+        scope.lines.pop();
+        scope.last = scope.previous;
+    }
+
+    /// Rewrites the operand of the instruction at `position`, used to fill
+    /// in jump targets.
+    fn change_operand(&mut self, position: usize, operand: usize) -> Result<(), CompileError> {
+        let scope = self.scope();
+        let opcode = Opcode::try_from(scope.instructions[position]).expect("valid opcode");
+
+        check_operands(opcode, &[operand])?;
+        let instruction = make(opcode, &[operand]);
+
+        scope.instructions[position..position + instruction.len()].copy_from_slice(&instruction);
+
+        return Ok(());
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(CompilationScope::default());
+
+        let outer = std::mem::take(&mut self.symbols);
+        self.symbols = SymbolTable::new_enclosed(outer);
+    }
+
+    fn leave_scope(&mut self) -> CompilationScope {
+        let scope = self.scopes.pop().expect("inside a function scope");
+
+        let outer = self.symbols.outer.take().expect("inside a function scope");
+        self.symbols = *outer;
+
+        return scope;
+    }
+}
+
+/// Fails when an operand is too large for the bytes its instruction has
+/// for it, which `make` would otherwise silently cut off.
+fn check_operands(opcode: Opcode, operands: &[usize]) -> Result<(), CompileError> {
+    for (operand, width) in operands.iter().zip(opcode.operand_widths()) {
+        let max = max_operand(*width);
+
+        if *operand > max {
+            // Indexes can reach `max` itself, so one more thing fits than
+            // for counts and positions.
+            let (what, limit) = match opcode {
+                Opcode::Constant | Opcode::Closure => ("constants", max + 1),
+                Opcode::GetGlobal | Opcode::SetGlobal | Opcode::CheckGlobal => {
+                    ("global variables", max + 1)
+                }
+                Opcode::GetLocal | Opcode::SetLocal => ("local variables in a function", max + 1),
+                Opcode::GetUpvalue | Opcode::SetUpvalue => {
+                    ("captured variables in a function", max + 1)
+                }
+                Opcode::GetBuiltin => ("builtins", max + 1),
+                Opcode::EnterLoop | Opcode::ExitLoop | Opcode::UnwindLoop => {
+                    ("nested loops", max + 1)
+                }
+                Opcode::Jump | Opcode::JumpNotTruthy | Opcode::IterNext => {
+                    ("bytes of bytecode in a function", max)
+                }
+                Opcode::Array => ("array elements", max),
+                Opcode::Hash => ("hash keys and values", max),
+                Opcode::Call => ("call arguments", max),
+                Opcode::SetIndex => ("indexes in an assignment", max),
+                _ => unreachable!("{} has no operands", opcode.name()),
+            };
+
+            return Err(CompileError::TooMany { what, limit });
+        }
+    }
+
+    return Ok(());
+}
+
+fn infix_opcode(operator: Operator) -> Opcode {
+    match operator {
+        Operator::Plus => return Opcode::Add,
+        Operator::Minus => return Opcode::Sub,
+        Operator::Multiply => return Opcode::Mul,
+        Operator::Divide => return Opcode::Div,
+        Operator::Modulo => return Opcode::Mod,
+        Operator::Power => return Opcode::Pow,
+        Operator::Equals => return Opcode::Equal,
+        Operator::NotEquals => return Opcode::NotEqual,
+        Operator::Gt => return Opcode::GreaterThan,
+        Operator::Lt => return Opcode::LessThan,
+        Operator::GtEq => return Opcode::GreaterEqual,
+        Operator::LtEq => return Opcode::LessEqual,
+        Operator::Not | Operator::And | Operator::Or => {
+            unreachable!("{} is not compiled to a single opcode", operator)
+        }
+    }
+}
+
+/// Adds the names the `let`s and `for`s in `block` bind to `names`,
+/// including those in nested blocks but not in nested functions.
+fn collect_bindings(block: &BlockStatement, names: &mut HashSet<String>) {
+    for statement in &block.statements {
+        match statement {
+            Statement::Let { name, value, .. } => {
+                names.insert(name.to_string());
+                collect_expression_bindings(value, names);
+            }
+            Statement::Return(value, _) | Statement::Expression(value, _) => {
+                collect_expression_bindings(value, names)
+            }
+            Statement::While {
+                condition, body, ..
+            } => {
+                collect_expression_bindings(condition, names);
+                collect_bindings(body, names);
+            }
+            Statement::For {
+                variable,
+                iterable,
+                body,
+                ..
+            } => {
+                names.insert(variable.to_string());
+                collect_expression_bindings(iterable, names);
+                collect_bindings(body, names);
+            }
+            Statement::Break(_) | Statement::Continue(_) => {}
+        }
+    }
+}
+
+fn collect_expression_bindings(expression: &Expresion, names: &mut HashSet<String>) {
+    match expression {
+        Expresion::Identifer(..)
+        | Expresion::Interger(..)
+        | Expresion::String(..)
+        | Expresion::Bool { .. }
+        | Expresion::Function { .. } => {}
+        Expresion::Prefix(_, right, _) => collect_expression_bindings(right, names),
+        Expresion::Infix(left, _, right, _)
+        | Expresion::Index {
+            left, index: right, ..
+        }
+        | Expresion::Assign {
+            target: left,
+            value: right,
+            ..
+        } => {
+            collect_expression_bindings(left, names);
+            collect_expression_bindings(right, names);
+        }
+        Expresion::If {
+            condition,
+            consequence,
+            alternative,
+            ..
+        } => {
+            collect_expression_bindings(condition, names);
+            collect_bindings(consequence, names);
+
+            if let Some(alternative) = alternative {
+                collect_bindings(alternative, names);
+            }
+        }
+        Expresion::Call { function, args, .. } => {
+            collect_expression_bindings(function, names);
+
+            for arg in args {
+                collect_expression_bindings(arg, names);
+            }
+        }
+        Expresion::Array(elements, _) => {
+            for element in elements {
+                collect_expression_bindings(element, names);
+            }
+        }
+        Expresion::Hash(pairs, _) => {
+            for (key, value) in pairs {
+                collect_expression_bindings(key, names);
+                collect_expression_bindings(value, names);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bytecode, CompileError, Compiler};
+    use crate::{
+        code::{make, Opcode},
+        disassembler::disassemble_source,
+        object::{Capture, Object},
+        parser::Parser,
+        vm::Vm,
+    };
+
+    fn compile(input: &str) -> Result<Bytecode, CompileError> {
+        let mut parser = Parser::new(input);
+        let program = parser.parse_program();
+
+        assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+
+        return Compiler::new().compile(&program);
+    }
+
+    fn concat(instructions: Vec<Vec<u8>>) -> Vec<u8> {
+        return instructions.into_iter().flatten().collect();
+    }
+
+    #[test]
+    fn test_compile_integer_arithmetic() {
+        let bytecode = compile("1 + 2; -3").unwrap();
+
+        assert_eq!(
+            bytecode.constants,
+            vec![Object::Integer(1), Object::Integer(2), Object::Integer(3)]
+        );
+        assert_eq!(
+            bytecode.instructions,
+            concat(vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Add, &[]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Minus, &[]),
+                make(Opcode::Pop, &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_compile_conditionals() {
+        let bytecode = compile("if (true) { 10 }; 3333;").unwrap();
+
+        assert_eq!(
+            bytecode.instructions,
+            concat(vec![
+                // 0000
+                make(Opcode::True, &[]),
+                // 0001
+                make(Opcode::JumpNotTruthy, &[10]),
+                // 0004
+                make(Opcode::Constant, &[0]),
+                // 0007
+                make(Opcode::Jump, &[11]),
+                // 0010
+                make(Opcode::Null, &[]),
+                // 0011
+                make(Opcode::Pop, &[]),
+                // 0012
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Pop, &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_compile_let_statements() {
+        let bytecode = compile("let one = 1; let two = one;").unwrap();
+
+        assert_eq!(
+            bytecode.instructions,
+            concat(vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::SetGlobal, &[1]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Pop, &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_compile_functions() {
+        let bytecode = compile("let f = fn(a) { let b = a; b * 2 }; f(1)").unwrap();
+
+        let Object::CompiledFunction(function) = &bytecode.constants[1] else {
+            panic!("Expected compiled function got {:?}", bytecode.constants[1]);
+        };
+
+        assert_eq!(function.num_params, 1);
+        assert_eq!(function.num_locals, 2);
+        assert_eq!(
+            function.instructions,
+            concat(vec![
+                make(Opcode::GetLocal, &[0]),
+                make(Opcode::SetLocal, &[1]),
+                make(Opcode::GetLocal, &[1]),
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Mul, &[]),
+                make(Opcode::ReturnValue, &[]),
+            ])
+        );
+        assert_eq!(
+            bytecode.instructions,
+            concat(vec![
+                make(Opcode::Closure, &[1]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Call, &[1]),
+                make(Opcode::Pop, &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_compile_empty_function_returns_null() {
+        let bytecode = compile("fn() { }").unwrap();
+
+        let Object::CompiledFunction(function) = &bytecode.constants[0] else {
+            panic!("Expected compiled function got {:?}", bytecode.constants[0]);
+        };
+
+        assert_eq!(
+            function.instructions,
+            concat(vec![
+                make(Opcode::Null, &[]),
+                make(Opcode::ReturnValue, &[])
+            ])
+        );
+    }
+
+    #[test]
+    fn test_compile_forward_globals() {
+        // `b` gets a global slot on first use, its `let` then reuses it.
+        let input = "let f = fn() { b }; let b = 2; f()";
+
+        let expected = "== main ==
+0000    1 OpClosure 0 (compiled fn/0)
+0003    | OpSetGlobal 0
+0006    | OpConstant 1 (2)
+0009    | OpSetGlobal 1
+0012    | OpGetGlobal 0
+0015    | OpCall 0
+0017    | OpPop
+
+== constant 0: compiled fn/0 ==
+0000    1 OpGetGlobal 1
+0003    | OpReturnValue
+";
+
+        assert_eq!(disassemble_source(input), Ok(String::from(expected)));
+        assert_eq!(
+            compile(input).unwrap().global_names,
+            vec![String::from("f"), String::from("b")]
+        );
+
+        assert!(compile("fn(x) { fn() { y } }").is_ok());
+        assert!(compile("let f = fn() { n += 1 }; let n = 0;").is_ok());
+        // Whether `n` exists is only known once the assignment runs.
+        assert!(compile("let f = fn() { n += 1 }; let m = 0;").is_ok());
+    }
+
+    fn repeat(count: usize, item: impl Fn(usize) -> String, separator: &str) -> String {
+        return (0..count).map(item).collect::<Vec<_>>().join(separator);
+    }
+
+    /// A distinct name for every `index`, identifiers cannot hold digits.
+    fn identifier(mut index: usize) -> String {
+        let mut name = String::from("v");
+
+        loop {
+            name.push((b'a' + (index % 26) as u8) as char);
+            index /= 26;
+
+            if index == 0 {
+                return name;
+            }
+        }
+    }
+
+    #[test]
+    fn test_compile_operand_limits() {
+        let too_many = |what, limit| Err(CompileError::TooMany { what, limit });
+
+        let constants = |count| repeat(count, |i| i.to_string(), "; ");
+        let input = format!("{}; 12345", constants(65535));
+        let bytecode = compile(&input).unwrap();
+        assert_eq!(Vm::new(bytecode).run(), Ok(Object::Integer(12345)));
+        assert_eq!(compile(&constants(65537)), too_many("constants", 65536));
+
+        let globals = |count| repeat(count, |i| format!("let {} = true;", identifier(i)), " ");
+        assert!(compile(&globals(65536)).is_ok());
+        assert_eq!(
+            compile(&globals(65537)),
+            too_many("global variables", 65536)
+        );
+
+        let locals = |count| format!("fn() {{ {} }}", globals(count));
+        assert!(compile(&locals(256)).is_ok());
+        assert_eq!(
+            compile(&locals(257)),
+            too_many("local variables in a function", 256)
+        );
+
+        // The innermost function captures the 256 locals of the outer one
+        // through the middle one, plus `b` to go over the limit.
+        let captures = |extra| {
+            let uses = repeat(256, identifier, ", ");
+            format!(
+                "fn() {{ {} fn() {{ let b = 1; fn() {{ [{}{}] }} }} }}",
+                globals(256),
+                uses,
+                extra
+            )
+        };
+        assert!(compile(&captures("")).is_ok());
+        assert_eq!(
+            compile(&captures(", b")),
+            too_many("captured variables in a function", 256)
+        );
+
+        // The jump over the alternative lands 2 bytes per statement plus
+        // 7 bytes in.
+        let jump = |count| format!("if (true) {{ {} }}", repeat(count, |_| "true".into(), "; "));
+        assert!(compile(&jump(32764)).is_ok());
+        assert_eq!(
+            compile(&jump(32765)),
+            too_many("bytes of bytecode in a function", 65535)
+        );
+
+        let array = |count| format!("[{}]", repeat(count, |_| "true".into(), ", "));
+        assert!(compile(&array(65535)).is_ok());
+        assert_eq!(compile(&array(65536)), too_many("array elements", 65535));
+
+        let hash = |count| format!("{{{}}}", repeat(count, |_| "true: true".into(), ", "));
+        assert!(compile(&hash(32767)).is_ok());
+        assert_eq!(
+            compile(&hash(32768)),
+            too_many("hash keys and values", 65535)
+        );
+    }
+
+    #[test]
+    fn test_compile_assignments() {
+        let input = "let x = 1;\nx += 2;\nlet xs = [x];\nxs[0] = 5;";
+
+        let expected = "== main ==
+0000    1 OpConstant 0 (1)
+0003    | OpSetGlobal 0
+0006    2 OpCheckGlobal 0
+0009    | OpGetGlobal 0
+0012    | OpConstant 1 (2)
+0015    | OpAdd
+0016    | OpSetGlobal 0
+0019    | OpGetGlobal 0
+0022    | OpPop
+0023    3 OpGetGlobal 0
+0026    | OpArray 1
+0029    | OpSetGlobal 1
+0032    4 OpCheckGlobal 1
+0035    | OpConstant 2 (0)
+0038    | OpConstant 3 (5)
+0041    | OpGetGlobal 1
+0044    | OpNull
+0045    | OpSetGlobal 1
+0048    | OpSetIndex 1 4
+0051    | OpSetGlobal 1
+0054    | OpPop
+";
+
+        assert_eq!(disassemble_source(input), Ok(String::from(expected)));
+
+        let expected = "== constant 1: compiled fn/0 ==
+0000    1 OpGetUpvalue 0
+0002    | OpConstant 0 (1)
+0005    | OpSub
+0006    | OpSetUpvalue 0
+0008    | OpGetUpvalue 0
+0010    | OpReturnValue
+";
+
+        let listing = disassemble_source("fn(n) { fn() { n -= 1 } }").unwrap();
+        assert!(listing.contains(expected), "{}", listing);
+
+        let expected = "== constant 1: compiled fn/0 ==
+0000    1 OpCheckGlobal 0
+0003    | OpConstant 0 (1)
+0006    | OpSetGlobal 0
+0009    | OpGetGlobal 0
+0012    | OpReturnValue
+";
+
+        let listing = disassemble_source("fn() { x = 1 }").unwrap();
+        assert!(listing.contains(expected), "{}", listing);
+    }
+
+    #[test]
+    fn test_compile_loops() {
+        let input = "while (true) { if (false) { break; } continue; }";
+
+        let expected = "== main ==
+0000    1 OpEnterLoop 0
+0002    | OpTrue
+0003    | OpJumpNotTruthy 28
+0006    | OpFalse
+0007    | OpJumpNotTruthy 17
+0010    | OpJump 28
+0013    | OpNull
+0014    | OpJump 18
+0017    | OpNull
+0018    | OpPop
+0019    | OpUnwindLoop 0 0
+0022    | OpJump 2
+0025    | OpJump 2
+0028    | OpExitLoop 0
+0030    | OpNull
+0031    | OpPop
+";
+
+        assert_eq!(disassemble_source(input), Ok(String::from(expected)));
+
+        let expected = "== main ==
+0000    1 OpArray 0
+0003    | OpSetGlobal 0
+0006    | OpEnterLoop 0
+0008    | OpGetGlobal 0
+0011    | OpIterInit
+0012    | OpIterNext 21
+0015    | OpSetGlobal 1
+0018    | OpJump 12
+0021    | OpExitLoop 0
+0023    | OpNull
+0024    | OpPop
+";
+
+        assert_eq!(
+            disassemble_source("let xs = []; for (x in xs) { }"),
+            Ok(String::from(expected))
+        );
+    }
+
+    #[test]
+    fn test_compile_closures() {
+        let input = "fn(a) { fn(b) { fn(c) { a + b + c } } }";
+
+        let expected = "== main ==
+0000    1 OpClosure 2 (compiled fn/1)
+0003    | OpPop
+
+== constant 0: compiled fn/1 ==
+0000    1 OpGetUpvalue 0
+0002    | OpGetUpvalue 1
+0004    | OpAdd
+0005    | OpGetLocal 0
+0007    | OpAdd
+0008    | OpReturnValue
+
+== constant 1: compiled fn/1 ==
+0000    1 OpClosure 0 (compiled fn/1)
+0003    | OpReturnValue
+
+== constant 2: compiled fn/1 ==
+0000    1 OpClosure 1 (compiled fn/1)
+0003    | OpReturnValue
+";
+
+        assert_eq!(disassemble_source(input), Ok(String::from(expected)));
+
+        let bytecode = compile(input).unwrap();
+        let captures: Vec<_> = bytecode
+            .constants
+            .iter()
+            .map(|constant| match constant {
+                Object::CompiledFunction(function) => function.captures.clone(),
+                _ => panic!("Expected compiled function got {:?}", constant),
+            })
+            .collect();
+
+        assert_eq!(
+            captures,
+            vec![
+                vec![
+                    Capture {
+                        is_local: false,
+                        index: 0,
+                    },
+                    Capture {
+                        is_local: true,
+                        index: 0,
+                    },
+                ],
+                vec![Capture {
+                    is_local: true,
+                    index: 0,
+                }],
+                vec![],
+            ]
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! Conversions between script values and Rust types, used by host functions
+//! to read their arguments and build their results.
+
+use std::{collections::HashMap, hash::Hash, rc::Rc};
+
+use crate::{
+    evaluator::RuntimeError,
+    object::{HashKey, Value},
+};
+
+/// Rust types that can be read out of a script value.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self, RuntimeError>;
+}
+
+/// Rust types that can be handed back to scripts.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+/// Rust types that can be used as hash keys.
+pub trait IntoHashKey {
+    fn into_hash_key(self) -> HashKey;
+}
+
+fn unexpected(expected: &'static str, value: &Value) -> RuntimeError {
+    return RuntimeError::UnexpectedType {
+        expected,
+        got: value.type_name(),
+    };
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Self, RuntimeError> {
+        return Ok(value.clone());
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        return self;
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Integer(value) => return Ok(*value),
+            _ => return Err(unexpected("INTEGER", value)),
+        }
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        return Value::Integer(self);
+    }
+}
+
+impl IntoHashKey for i64 {
+    fn into_hash_key(self) -> HashKey {
+        return HashKey::Integer(self);
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Boolean(value) => return Ok(*value),
+            _ => return Err(unexpected("BOOLEAN", value)),
+        }
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        return Value::Boolean(self);
+    }
+}
+
+impl IntoHashKey for bool {
+    fn into_hash_key(self) -> HashKey {
+        return HashKey::Boolean(self);
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::String(value) => return Ok(value.to_owned()),
+            _ => return Err(unexpected("STRING", value)),
+        }
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        return Value::String(self);
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        return Value::String(self.to_owned());
+    }
+}
+
+impl IntoHashKey for String {
+    fn into_hash_key(self) -> HashKey {
+        return HashKey::String(self);
+    }
+}
+
+impl IntoHashKey for &str {
+    fn into_hash_key(self) -> HashKey {
+        return HashKey::String(self.to_owned());
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        return Value::Null;
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Array(elements) => return elements.iter().map(T::from_value).collect(),
+            _ => return Err(unexpected("ARRAY", value)),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        let elements = self.into_iter().map(IntoValue::into_value).collect();
+
+        return Value::Array(Rc::new(elements));
+    }
+}
+
+impl<K: FromValue + Eq + Hash, V: FromValue> FromValue for HashMap<K, V> {
+    fn from_value(value: &Value) -> Result<Self, RuntimeError> {
+        let Value::Hash(pairs) = value else {
+            return Err(unexpected("HASH", value));
+        };
+
+        let mut map = HashMap::with_capacity(pairs.len());
+
+        for (key, value) in pairs.iter() {
+            let key = K::from_value(&Value::from(key.clone()))?;
+            map.insert(key, V::from_value(value)?);
+        }
+
+        return Ok(map);
+    }
+}
+
+impl<K: IntoHashKey, V: IntoValue> IntoValue for HashMap<K, V> {
+    fn into_value(self) -> Value {
+        let pairs = self
+            .into_iter()
+            .map(|(key, value)| (key.into_hash_key(), value.into_value()))
+            .collect();
+
+        return Value::Hash(Rc::new(pairs));
+    }
+}
+
+/// A fixed list of typed arguments, checked for count and types in one go.
+///
+/// ```
+/// use interpeter::{convert::FromArgs, Value};
+///
+/// let args = [Value::Integer(2), Value::String(String::from("ab"))];
+/// let (times, text) = <(i64, String)>::from_args(&args).unwrap();
+///
+/// assert_eq!(text.repeat(times as usize), "abab");
+/// ```
+pub trait FromArgs: Sized {
+    fn from_args(args: &[Value]) -> Result<Self, RuntimeError>;
+}
+
+macro_rules! impl_from_args {
+    ($count:expr $(, $name:ident)*) => {
+        impl<$($name: FromValue),*> FromArgs for ($($name,)*) {
+            #[allow(unused_variables, unused_mut, clippy::unused_unit)]
+            fn from_args(args: &[Value]) -> Result<Self, RuntimeError> {
+                if args.len() != $count {
+                    return Err(RuntimeError::WrongArgumentCount {
+                        expected: $count,
+                        got: args.len(),
+                    });
+                }
+
+                let mut args = args.iter();
+
+                return Ok(($($name::from_value(args.next().unwrap())?,)*));
+            }
+        }
+    };
+}
+
+impl_from_args!(0);
+impl_from_args!(1, A);
+impl_from_args!(2, A, B);
+impl_from_args!(3, A, B, C);
+impl_from_args!(4, A, B, C, D);
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, rc::Rc};
+
+    use super::{FromArgs, FromValue, IntoValue};
+    use crate::{
+        evaluator::RuntimeError,
+        object::{HashKey, Value},
+    };
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(i64::from_value(&42.into_value()), Ok(42));
+        assert_eq!(bool::from_value(&true.into_value()), Ok(true));
+        assert_eq!(
+            String::from_value(&"monkey".into_value()),
+            Ok(String::from("monkey"))
+        );
+        assert_eq!(
+            Vec::<i64>::from_value(&vec![1, 2, 3].into_value()),
+            Ok(vec![1, 2, 3])
+        );
+
+        let map = HashMap::from([(String::from("a"), vec![true]), (String::from("b"), vec![])]);
+        assert_eq!(
+            HashMap::<String, Vec<bool>>::from_value(&map.clone().into_value()),
+            Ok(map)
+        );
+    }
+
+    #[test]
+    fn test_into_value_shapes() {
+        assert_eq!(
+            vec![1, 2].into_value(),
+            Value::Array(Rc::new(vec![Value::Integer(1), Value::Integer(2)]))
+        );
+        assert_eq!(
+            HashMap::from([(1, "one")]).into_value(),
+            Value::Hash(Rc::new(HashMap::from([(
+                HashKey::Integer(1),
+                Value::String(String::from("one"))
+            )])))
+        );
+    }
+
+    #[test]
+    fn test_type_errors() {
+        assert_eq!(
+            i64::from_value(&Value::Boolean(true)),
+            Err(RuntimeError::UnexpectedType {
+                expected: "INTEGER",
+                got: "BOOLEAN",
+            })
+        );
+        assert_eq!(
+            Vec::<String>::from_value(&vec![1].into_value()),
+            Err(RuntimeError::UnexpectedType {
+                expected: "STRING",
+                got: "INTEGER",
+            })
+        );
+        assert_eq!(
+            HashMap::<i64, i64>::from_value(&HashMap::from([("a", 1)]).into_value()),
+            Err(RuntimeError::UnexpectedType {
+                expected: "INTEGER",
+                got: "STRING",
+            })
+        );
+    }
+
+    #[test]
+    fn test_from_args() {
+        let args = [Value::Integer(1), Value::Boolean(false)];
+
+        assert_eq!(<(i64, bool)>::from_args(&args), Ok((1, false)));
+        assert_eq!(<()>::from_args(&[]), Ok(()));
+        assert_eq!(
+            <(i64,)>::from_args(&args),
+            Err(RuntimeError::WrongArgumentCount {
+                expected: 1,
+                got: 2,
+            })
+        );
+        assert_eq!(
+            <(bool, bool)>::from_args(&args),
+            Err(RuntimeError::UnexpectedType {
+                expected: "BOOLEAN",
+                got: "INTEGER",
+            })
+        );
+    }
+}
@@ -0,0 +1,580 @@
+//! Programs with the result they must produce, run against both the
+//! tree-walking evaluator and the bytecode VM so the two cannot drift
+//! apart. Results are compared as printed, errors as `error: <message>`.
+
+pub const CASES: &[(&str, &str)] = &[
+    // Integers
+    ("5", "5"),
+    ("-10", "-10"),
+    ("5 + 5 + 5 + 5 - 10", "10"),
+    ("2 * 2 * 2 * 2 * 2", "32"),
+    ("-50 + 100 + -50", "0"),
+    ("50 / 2 * 2 + 10", "60"),
+    ("-7 / 2", "-3"),
+    ("(5 + 10 * 2 + 15 / 3) * 2 + -10", "50"),
+    ("7 % 3", "1"),
+    ("-7 % 3", "-1"),
+    ("7 % -3", "1"),
+    ("6 % 3", "0"),
+    ("1 + 10 % 4 * 2", "5"),
+    ("2 ** 10", "1024"),
+    ("2 ** 3 ** 2", "512"),
+    ("(2 ** 3) ** 2", "64"),
+    ("-2 ** 2", "-4"),
+    ("(-2) ** 3", "-8"),
+    ("2 ** 0", "1"),
+    ("10 - 2 ** 3 * 2", "-6"),
+    ("1 ** 10000000000", "1"),
+    ("(-1) ** 10000000001", "-1"),
+    ("5 % 0", "error: division by zero"),
+    ("2 ** -1", "error: exponent must not be negative, got -1"),
+    ("2 ** 64", "error: integer overflow"),
+    ("9223372036854775807", "9223372036854775807"),
+    ("-9223372036854775808", "-9223372036854775808"),
+    ("-9223372036854775808 + 1", "-9223372036854775807"),
+    ("3037000499 * 3037000499", "9223372030926249001"),
+    ("-9223372036854775808 % -1", "error: integer overflow"),
+    ("9223372036854775807 + 1", "error: integer overflow"),
+    ("-9223372036854775808 - 1", "error: integer overflow"),
+    ("4611686018427387904 * 2", "error: integer overflow"),
+    ("-9223372036854775808 * -1", "error: integer overflow"),
+    ("-9223372036854775808 / -1", "error: integer overflow"),
+    ("-(-9223372036854775808)", "error: integer overflow"),
+    (
+        "let x = 9223372036854775807; x -= 1; x += 2",
+        "error: integer overflow",
+    ),
+    (
+        "let xs = [9223372036854775807]; xs[0] *= 2",
+        "error: integer overflow",
+    ),
+    // Booleans
+    ("true", "true"),
+    ("1 < 2", "true"),
+    ("1 > 2", "false"),
+    ("1 <= 1", "true"),
+    ("2 <= 1", "false"),
+    ("1 >= 1", "true"),
+    ("1 >= 2", "false"),
+    ("1 + 1 >= 2 && 3 % 2 == 1", "true"),
+    ("1 == 1", "true"),
+    ("1 != 2", "true"),
+    ("true != false", "true"),
+    ("(1 < 2) == true", "true"),
+    ("!true", "false"),
+    ("!5", "false"),
+    ("!!5", "true"),
+    ("true && true", "true"),
+    ("true && false", "false"),
+    ("false || true", "true"),
+    ("false || false", "false"),
+    ("1 && \"a\"", "true"),
+    ("0 || if (false) { 1 }", "true"),
+    ("if (false) { 1 } || if (false) { 1 }", "false"),
+    ("1 < 2 && 2 < 3", "true"),
+    ("1 == 2 || 3 > 2 && 2 > 1", "true"),
+    ("(true || false) && false", "false"),
+    ("!true || !false", "true"),
+    ("true || 1 / 0", "true"),
+    ("false && 1 / 0 || true", "true"),
+    ("true && 1 / 0", "error: division by zero"),
+    ("false && undefined_function()", "false"),
+    (
+        "let calls = 0; let hit = fn(v) { calls += 1; v }; hit(false) && hit(true); hit(true) || hit(true); calls",
+        "2",
+    ),
+    (
+        "let xs = []; if (len(xs) > 0 && xs[0] == 1) { 1 } else { 2 }",
+        "2",
+    ),
+    // Strings
+    (r#""Hello" + " " + "World!""#, "Hello World!"),
+    (r#""a" == "a""#, "true"),
+    (r#""a" != "b""#, "true"),
+    // Conditionals
+    ("if (true) { 10 }", "10"),
+    ("if (false) { 10 }", "null"),
+    ("if (1) { 10 }", "10"),
+    ("if (1 > 2) { 10 } else { 20 }", "20"),
+    ("if (1 > 2) { 10 } else if (2 > 1) { 15 } else { 20 }", "15"),
+    ("if (true) { }", "null"),
+    ("if (true) { let a = 1; }", "null"),
+    ("if ((if (false) { 10 })) { 10 } else { 20 }", "20"),
+    // Return
+    ("return 10; 9;", "10"),
+    ("9; return 2 * 5; 9;", "10"),
+    ("if (10 > 1) { if (10 > 1) { return 10; } return 1; }", "10"),
+    // Bindings
+    ("let a = 5; a;", "5"),
+    ("let a = 5; let b = a; let c = a + b + 5; c;", "15"),
+    ("let a = 5;", "null"),
+    ("let a = 5; a; let b = 1;", "null"),
+    ("let x = if (false) { 1 } else { 2 }; x * 3", "6"),
+    ("let y = y;", "error: identifier not found: y"),
+    ("let x = 1; let x = x + 1; x", "2"),
+    ("let len = len; len(\"ab\")", "2"),
+    (
+        "let y = if (true) { let y = 2; y + 1 }; y",
+        "3",
+    ),
+    (
+        "let f = fn() { let y = y; y }; f()",
+        "error: identifier not found: y",
+    ),
+    (
+        "let x = 1; let f = fn() { let x = x + 1; x }; f() + x",
+        "3",
+    ),
+    (
+        "let f = fn() { g() }; let g = fn() { 5 }; f()",
+        "5",
+    ),
+    (
+        "let isEven = fn(n) { if (n == 0) { true } else { isOdd(n - 1) } };
+        let isOdd = fn(n) { if (n == 0) { false } else { isEven(n - 1) } };
+        [isEven(10), isOdd(7)]",
+        "[true, true]",
+    ),
+    (
+        "let f = fn() { later }; f()",
+        "error: identifier not found: later",
+    ),
+    (
+        "let f = fn() { later }; let a = f(); let later = 1;",
+        "error: identifier not found: later",
+    ),
+    ("let f = fn() { nowhere }; 1", "1"),
+    ("if (false) { nowhere }", "null"),
+    (
+        "let add = fn(x) { total += x; }; let total = 0; add(2); add(3); total",
+        "5",
+    ),
+    (
+        "let f = fn() { let g = fn() { h() }; let h = fn() { 1 }; g() }; f()",
+        "1",
+    ),
+    (
+        "let f = fn() {
+            let g = fn() { let k = fn() { h() }; k() };
+            let h = fn() { 1 };
+            g()
+        };
+        f()",
+        "1",
+    ),
+    (
+        "let f = fn() {
+            let g = fn() { let k = fn() { h }; let h = 2; k() };
+            let h = 1;
+            g()
+        };
+        f()",
+        "2",
+    ),
+    (
+        "let h = 0; let f = fn() { let g = fn() { h }; let h = 4; g() }; [f(), h]",
+        "[4, 0]",
+    ),
+    (
+        "let f = fn() { let g = fn() { h }; if (true) { let h = 3; }; g() }; f()",
+        "3",
+    ),
+    // Functions
+    ("let identity = fn(x) { x; }; identity(5);", "5"),
+    (
+        "let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));",
+        "20",
+    ),
+    ("fn(x) { x; }(5)", "5"),
+    ("let f = fn() { return 1; 2 }; f() + 10", "11"),
+    ("let f = fn() { }; f()", "null"),
+    ("let f = fn() { let a = 1; }; f()", "null"),
+    ("let f = fn(a) { if (a) { return 1; } 2 }; f(false)", "2"),
+    (
+        "let one = fn() { 1 }; let two = fn() { one() + one() }; two()",
+        "2",
+    ),
+    (
+        "let g = 10; let f = fn(a) { let b = a + g; b * 2 }; f(1) + f(2)",
+        "46",
+    ),
+    (
+        "let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) }; fib(15)",
+        "610",
+    ),
+    (
+        "let count = fn(n) { if (n == 0) { 0 } else { 1 + count(n - 1) } }; count(1000)",
+        "1000",
+    ),
+    (
+        "let count = fn(n) { if (n == 0) { 0 } else { 1 + count(n - 1) } }; count(1024)",
+        "error: stack overflow",
+    ),
+    ("let f = fn(n) { f(n + 1) }; f(0)", "error: stack overflow"),
+    // Closures
+    (
+        "let newAdder = fn(x) { fn(y) { x + y } }; let addTwo = newAdder(2); addTwo(3)",
+        "5",
+    ),
+    (
+        "let add = fn(a) { fn(b) { fn(c) { a + b + c } } }; add(1)(2)(3)",
+        "6",
+    ),
+    (
+        "let counter = fn(start) { fn(step) { start + step } };
+        let tens = counter(10); let twenties = counter(20);
+        tens(1) + twenties(2) + tens(3)",
+        "46",
+    ),
+    (
+        "let mk = fn(x) { fn() { x } }; let one = mk(1); let two = mk(2); one() + two() * 10",
+        "21",
+    ),
+    (
+        "let f = fn() { let x = 7; fn() { x * 2 } }; let g = f(); g()",
+        "14",
+    ),
+    ("let a = fn(x) { fn() { fn() { x } } }; a(9)()()", "9"),
+    (
+        "let counter = fn() { let n = 0; fn() { n += 1; n } }; let c = counter(); [c(), c(), c()]",
+        "[1, 2, 3]",
+    ),
+    (
+        "let counter = fn() { let n = 0; fn() { n += 1; n } };
+        let a = counter(); let b = counter();
+        [a(), a(), b(), a(), b()]",
+        "[1, 2, 1, 3, 2]",
+    ),
+    (
+        "let pair = fn() { let n = 0; [fn() { n += 1; n }, fn() { n += 10; n }] };
+        let p = pair(); [p[0](), p[1](), p[0](), p[1]()]",
+        "[1, 11, 12, 22]",
+    ),
+    (
+        "let f = fn() {
+            let n = 0;
+            let inc = fn() { n += 1 };
+            let get = fn() { n };
+            inc(); inc();
+            let before = get();
+            n += 5;
+            [before, get(), inc(), n]
+        };
+        f()",
+        "[2, 7, 8, 8]",
+    ),
+    (
+        "let outer = fn() {
+            let fact = fn(n) { if (n < 2) { 1 } else { n * fact(n - 1) } };
+            fact(5)
+        };
+        outer()",
+        "120",
+    ),
+    (
+        "let wrapper = fn() {
+            let countDown = fn(x) { if (x == 0) { return 0; } countDown(x - 1) };
+            countDown;
+        };
+        wrapper()(3)",
+        "0",
+    ),
+    // Arrays
+    ("[]", "[]"),
+    ("[1, 2 * 2, 3 + 3]", "[1, 4, 6]"),
+    (r#"["a", [true, fn(x) { x }(1)]]"#, r#"["a", [true, 1]]"#),
+    ("[1, 2, 3][0]", "1"),
+    ("[1, 2, 3][1 + 1]", "3"),
+    ("let i = 0; [1][i]", "1"),
+    ("let xs = [1, 2, 3]; xs[0] + xs[1] + xs[2]", "6"),
+    ("let xs = [1, 2, 3]; let i = xs[0]; xs[i]", "2"),
+    ("[1, 2, 3][-1]", "3"),
+    ("[1, 2, 3][-3]", "1"),
+    ("[[1, 2], [3]][0][1]", "2"),
+    ("let f = fn() { [fn(x) { x * 2 }] }; f()[0](21)", "42"),
+    (
+        "[1, 2, 3][3]",
+        "error: index 3 out of bounds for array of length 3",
+    ),
+    (
+        "[1, 2, 3][-4]",
+        "error: index -4 out of bounds for array of length 3",
+    ),
+    (
+        "[][0]",
+        "error: index 0 out of bounds for array of length 0",
+    ),
+    (
+        r#"[1]["0"]"#,
+        "error: index operator not supported: ARRAY[STRING]",
+    ),
+    (
+        "1[0]",
+        "error: index operator not supported: INTEGER[INTEGER]",
+    ),
+    // Hashes
+    ("{}", "{}"),
+    (
+        r#"let two = "two"; {"one": 10 - 9, two: 1 + 1, "thr" + "ee": 6 / 2, 4: 4, true: 5, false: 6}"#,
+        r#"{4: 4, false: 6, true: 5, "one": 1, "three": 3, "two": 2}"#,
+    ),
+    (r#"{"a": 1, "a": 2}"#, r#"{"a": 2}"#),
+    (r#"{"foo": 5}["foo"]"#, "5"),
+    (r#"{"foo": 5}["bar"]"#, "null"),
+    (r#"let key = "foo"; {"foo": 5}[key]"#, "5"),
+    ("{}[\"foo\"]", "null"),
+    ("{5: 5}[5]", "5"),
+    ("{true: 5}[true]", "5"),
+    ("{false: 5}[1 > 2]", "5"),
+    (
+        r#"let config = {"server": {"ports": [80, 443]}}; config["server"]["ports"][-1]"#,
+        "443",
+    ),
+    (r#"len({"a": 1, "b": 2})"#, "2"),
+    ("type_of({})", "HASH"),
+    (
+        r#"{"name": "Monkey"}[fn(x) { x }]"#,
+        "error: unusable as hash key: FUNCTION, only INTEGER, STRING and BOOLEAN are",
+    ),
+    (
+        "{[1]: 2}",
+        "error: unusable as hash key: ARRAY, only INTEGER, STRING and BOOLEAN are",
+    ),
+    // Loops
+    ("while (false) { 1 }", "null"),
+    (
+        "let i = 0; let sum = 0; while (i < 5) { let sum = sum + i; let i = i + 1; } sum",
+        "10",
+    ),
+    ("let i = 0; while (i < 10000) { let i = i + 1; } i", "10000"),
+    (
+        "let xs = []; for (x in [1, 2, 3]) { let xs = push(xs, x * 2); } xs",
+        "[2, 4, 6]",
+    ),
+    (r#"let s = ""; for (c in "abc") { let s = c + s; } s"#, "cba"),
+    (
+        r#"let ks = []; for (k in {"b": 1, "a": 2, 3: 0}) { let ks = push(ks, k); } ks"#,
+        r#"[3, "a", "b"]"#,
+    ),
+    ("for (x in []) { x } ", "null"),
+    (
+        "let i = 0; while (true) { if (i == 3) { break; } let i = i + 1; } i",
+        "3",
+    ),
+    (
+        "let i = 0; let n = 0; while (i < 5) { let i = i + 1; if (i == 2) { continue; } let n = n + i; } n",
+        "13",
+    ),
+    (
+        "let sum = 0; for (x in [1, 2, 3, 4]) { if (x == 2) { continue; } let sum = sum + x; } sum",
+        "8",
+    ),
+    (
+        "let n = 0;
+        for (a in [1, 2]) {
+            for (b in [1, 2, 3]) { if (b == 2) { break; } let n = n + 1; }
+            let n = n + 10;
+        }
+        n",
+        "22",
+    ),
+    (
+        "let find = fn(xs, t) { for (x in xs) { if (x == t) { return true; } } false };
+        [find([1, 2], 2), find([1], 3)]",
+        "[true, false]",
+    ),
+    (
+        "let total = fn(xs) { let t = 0; for (x in xs) { let t = t + x; } t }; total([1, 2, 3])",
+        "6",
+    ),
+    (
+        "let f = fn() { let i = 0; while (true) { let i = i + 1; if (i > 2) { return i; } } }; f()",
+        "3",
+    ),
+    ("let x = 1; for (x in [5]) { } x", "5"),
+    (
+        "let n = 0; for (x in [1, 2, 3]) { let ys = [1, if (x == 2) { continue; }, 3]; n += len(ys); } n",
+        "6",
+    ),
+    (
+        "let i = 0; while (i < 3) { let x = if (true) { break; }; i += 1; } i",
+        "0",
+    ),
+    (
+        "let n = 0; for (x in [1, 2, 3]) { n += x * if (x == 2) { break; } else { 10 }; } n",
+        "10",
+    ),
+    (
+        "let f = fn() { let x = 1 + if (true) { return 5; }; 10 }; f()",
+        "5",
+    ),
+    ("let f = fn() { len([if (true) { return 7; }]) }; f()", "7"),
+    ("1 + if (true) { return 2; }", "2"),
+    (
+        "let f = fn() {
+            let n = 0;
+            for (x in [1, 2, 3, 4]) {
+                let n = n + len([x, if (x == 2) { continue; } else { x }]);
+                if (len([n, if (x == 3) { break; } else { 1 }]) == 0) { return -1; }
+            }
+            [n, 99]
+        };
+        f()[0] + f()[1]",
+        "103",
+    ),
+    ("for (x in 5) { }", "error: cannot loop over INTEGER"),
+    // Assignment
+    ("let x = 1; x = 2; x", "2"),
+    ("let x = 1; x = x + 1", "2"),
+    ("let a = 1; let b = 2; a = b = 5; a + b", "10"),
+    ("let x = 10; x += 5; x -= 3; x *= 2; x /= 4; x", "6"),
+    (
+        "let i = 0; let sum = 0; while (i < 5) { sum += i; i += 1; } sum",
+        "10",
+    ),
+    ("let f = fn(x) { x *= 2; x }; f(21)", "42"),
+    (
+        "let total = 0; let add = fn(x) { total += x; }; add(2); add(3); total",
+        "5",
+    ),
+    (
+        "let counter = fn() { let n = 0; fn() { n += 1; n } }; let c = counter(); c(); c(); c()",
+        "3",
+    ),
+    (
+        "let mk = fn() { let n = 0; fn() { n += 1 } }; let a = mk(); let b = mk(); a(); a(); b(); [a(), b()]",
+        "[3, 2]",
+    ),
+    (
+        "let f = fn() { let n = 0; [fn() { n += 1 }, fn() { n }] }; let p = f(); p[0](); p[0](); p[1]()",
+        "2",
+    ),
+    (
+        "let f = fn() { let n = 1; let g = fn() { fn() { n = n * 10 } }; g()(); g()(); n }; f()",
+        "100",
+    ),
+    ("let xs = [1, 2, 3]; xs[0] = 10; xs[-1] += 5; xs", "[10, 2, 8]"),
+    ("let xs = [0]; xs[0] = 7", "7"),
+    ("let a = [1]; let b = a; b[0] = 2; [a, b]", "[[1], [2]]"),
+    (
+        r#"let m = {"a": 1}; m["b"] = 2; m["a"] += 10; m"#,
+        r#"{"a": 11, "b": 2}"#,
+    ),
+    (
+        r#"let m = {"xs": [1, 2]}; m["xs"][1] *= 3; m["xs"]"#,
+        "[1, 6]",
+    ),
+    (
+        r#"let counts = {"a": 0, "b": 0, "c": 0}; for (c in "abca") { counts[c] += 1; } counts"#,
+        r#"{"a": 2, "b": 1, "c": 1}"#,
+    ),
+    (
+        "let f = fn() { let xs = [1, 2]; let g = fn() { xs[0] = 5; }; g(); xs }; f()",
+        "[5, 2]",
+    ),
+    ("y = 1", "error: assignment to undeclared variable: y"),
+    ("len = 1", "error: assignment to undeclared variable: len"),
+    ("if (false) { y = 1 }; 5", "5"),
+    ("let f = fn() { nope = 1 }; 5", "5"),
+    (
+        "let f = fn() { nope = 1 }; f()",
+        "error: assignment to undeclared variable: nope",
+    ),
+    (
+        "x = 1; let x = 2; x",
+        "error: assignment to undeclared variable: x",
+    ),
+    (
+        "let xs = [1]; if (false) { let ys = [1] }; ys[0] = xs",
+        "error: assignment to undeclared variable: ys",
+    ),
+    ("let f = fn() { n += 1 }; let n = 0; f(); n", "1"),
+    (
+        "let f = fn() { n += 1 }; f(); let n = 0;",
+        "error: assignment to undeclared variable: n",
+    ),
+    (
+        "let xs = [1]; xs[1] = 2",
+        "error: index 1 out of bounds for array of length 1",
+    ),
+    (
+        "let x = 1; x[0] = 2",
+        "error: index operator not supported: INTEGER[INTEGER]",
+    ),
+    (
+        "let m = {}; m[[1]] = 1",
+        "error: unusable as hash key: ARRAY, only INTEGER, STRING and BOOLEAN are",
+    ),
+    (
+        r#"let x = "a"; x -= 1"#,
+        "error: type mismatch: STRING - INTEGER",
+    ),
+    // Builtins
+    (r#"len("")"#, "0"),
+    (r#"len("four")"#, "4"),
+    (r#"len("héllo")"#, "5"),
+    (
+        r#"type_of(1) + type_of("a") + type_of(true)"#,
+        "INTEGERSTRINGBOOLEAN",
+    ),
+    ("type_of(fn() { 1 })", "FUNCTION"),
+    ("type_of(len)", "BUILTIN"),
+    ("let len = fn(x) { 42 }; len(\"abc\")", "42"),
+    ("let f = fn(s) { len(s) * 2 }; f(\"abc\")", "6"),
+    ("puts(\"hello\", 1)", "null"),
+    ("len([1, 2, 3])", "3"),
+    ("first([1, 2, 3])", "1"),
+    ("last([1, 2, 3])", "3"),
+    ("rest([1, 2, 3])", "[2, 3]"),
+    ("rest([])", "null"),
+    (
+        "let xs = [1]; let ys = push(xs, 2); len(xs) + len(ys) * 10",
+        "21",
+    ),
+    ("type_of([])", "ARRAY"),
+    (
+        "let map = fn(xs, f) {
+            let iter = fn(xs, acc) {
+                if (len(xs) == 0) { acc } else { iter(rest(xs), push(acc, f(first(xs)))) }
+            };
+            iter(xs, [])
+        };
+        map([1, 2, 3], fn(x) { x * x })",
+        "[1, 4, 9]",
+    ),
+    (
+        "len(1)",
+        "error: argument to `len` not supported, got INTEGER",
+    ),
+    (
+        r#"len("one", "two")"#,
+        "error: wrong number of arguments: expected 1, got 2",
+    ),
+    (
+        "first(1)",
+        "error: argument to `first` not supported, got INTEGER",
+    ),
+    (
+        "push(1, 1)",
+        "error: argument to `push` not supported, got INTEGER",
+    ),
+    // Errors
+    ("5 + true;", "error: type mismatch: INTEGER + BOOLEAN"),
+    ("5 + true; 5;", "error: type mismatch: INTEGER + BOOLEAN"),
+    ("-true", "error: unknown operator: -BOOLEAN"),
+    (
+        "true + false;",
+        "error: unknown operator: BOOLEAN + BOOLEAN",
+    ),
+    ("foobar", "error: identifier not found: foobar"),
+    ("10 / 0", "error: division by zero"),
+    (
+        r#""Hello" - "World""#,
+        "error: unknown operator: STRING - STRING",
+    ),
+    ("5(1)", "error: not a function: INTEGER"),
+    (
+        "let add = fn(a, b) { a + b }; add(1)",
+        "error: wrong number of arguments: expected 2, got 1",
+    ),
+];
@@ -0,0 +1,153 @@
+use crate::span::Span;
+
+/// An error that can point at the source it came from.
+pub trait Diagnostic: std::error::Error {
+    fn span(&self) -> Span;
+
+    fn hint(&self) -> Option<String> {
+        return None;
+    }
+}
+
+/// Renders `diagnostic` with the offending source line and a caret underline:
+///
+/// ```text
+/// error: expected `=` after the let binding name, found an integer
+///  --> line 1, column 7
+///   |
+/// 1 | let x 5;
+///   |       ^
+///   = hint: let bindings take the form `let <name> = <value>;`
+/// ```
+pub fn render(source: &str, diagnostic: &dyn Diagnostic) -> String {
+    let span = diagnostic.span();
+    let line = source
+        .lines()
+        .nth(span.line.saturating_sub(1))
+        .unwrap_or("");
+    let gutter = " ".repeat(span.line.to_string().len());
+
+    // Keep tabs so the caret lines up with the source line above it.
+    let indent: String = line
+        .chars()
+        .take(span.column.saturating_sub(1))
+        .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+        .collect();
+    let remaining = line
+        .chars()
+        .count()
+        .saturating_sub(span.column.saturating_sub(1));
+    let underlined = source.get(span.start..span.end).unwrap_or("");
+    let width = underlined.chars().count().min(remaining).max(1);
+
+    let mut output = format!("error: {}\n", diagnostic);
+    output += &format!("{}--> line {}, column {}\n", gutter, span.line, span.column);
+    output += &format!("{} |\n", gutter);
+    output += &format!("{} | {}\n", span.line, line);
+    output += &format!("{} | {}{}\n", gutter, indent, "^".repeat(width));
+
+    if let Some(hint) = diagnostic.hint() {
+        output += &format!("{} = hint: {}\n", gutter, hint);
+    }
+
+    return output;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+    use crate::{lexer::LexError, parser::Parser, span::Span};
+
+    fn render_first_error(input: &str) -> String {
+        let mut parser = Parser::new(input);
+        parser.parse_program();
+
+        return render(input, &parser.errors()[0]);
+    }
+
+    #[test]
+    fn test_render_parse_error() {
+        let expected = "error: expected `=` after the let binding name, found an integer
+ --> line 2, column 7
+  |
+2 | let x 5;
+  |       ^
+  = hint: let bindings take the form `let <name> = <value>;`
+";
+
+        assert_eq!(render_first_error("let a = 1;\nlet x 5;"), expected);
+    }
+
+    #[test]
+    fn test_render_underlines_whole_token() {
+        let expected = "error: integer literal `99999999999999999999` is out of range
+ --> line 1, column 9
+  |
+1 | let a = 99999999999999999999;
+  |         ^^^^^^^^^^^^^^^^^^^^
+  = hint: integers must be between -9223372036854775808 and 9223372036854775807
+";
+
+        assert_eq!(
+            render_first_error("let a = 99999999999999999999;"),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_render_lex_error_keeps_tabs() {
+        let expected = "error: illegal character `#`
+ --> line 1, column 4
+  |
+1 | \t1 # 2;
+  | \t  ^
+";
+
+        assert_eq!(render_first_error("\t1 # 2;"), expected);
+    }
+
+    #[test]
+    fn test_render_error_at_end_of_input() {
+        let expected = "error: expected an expression, found end of input
+ --> line 1, column 4
+  |
+1 | 1 +
+  |    ^
+";
+
+        assert_eq!(render_first_error("1 +"), expected);
+    }
+
+    #[test]
+    fn test_render_counts_chars_not_bytes() {
+        let input = "é ≠ 1;";
+        let mut parser = Parser::new(input);
+        parser.parse_program();
+
+        let expected = "error: illegal character `≠`
+ --> line 1, column 3
+  |
+1 | é ≠ 1;
+  |   ^
+";
+
+        assert_eq!(render(input, &parser.errors()[1]), expected);
+    }
+
+    #[test]
+    fn test_render_column_zero() {
+        let error = LexError::UnterminatedString {
+            span: Span::new(0, 1, 1, 0),
+        };
+
+        let expected = "error: unterminated string literal
+ --> line 1, column 0
+  |
+1 | \"abc
+  | ^
+  = hint: add a closing `\"` to end the string
+";
+
+        assert_eq!(render("\"abc", &error), expected);
+    }
+}
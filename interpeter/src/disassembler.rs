@@ -0,0 +1,202 @@
+//! Human readable listings of compiled bytecode. Each line shows the
+//! offset, the source line (`|` when it is the same as the line above),
+//! the opcode and its operands, with constants resolved:
+//!
+//! ```text
+//! == main ==
+//! 0000    1 OpConstant 0 (5)
+//! 0003    | OpSetGlobal 0
+//! ```
+//!
+//! Function constants get a listing of their own after the main program.
+
+use std::fmt::{self, Write};
+
+use crate::{
+    code::{read_operands, Opcode},
+    compiler::{Bytecode, Compiler},
+    interpreter::{parse, Error},
+    object::Object,
+};
+
+pub fn disassemble(bytecode: &Bytecode) -> String {
+    let mut output = String::new();
+
+    write_bytecode(&mut output, bytecode).expect("writing to a String cannot fail");
+
+    return output;
+}
+
+/// Parses and compiles `source`, then disassembles it.
+pub fn disassemble_source(source: &str) -> Result<String, Error> {
+    let program = parse(source)?;
+    let bytecode = Compiler::new().compile(&program)?;
+
+    return Ok(disassemble(&bytecode));
+}
+
+fn write_bytecode(output: &mut String, bytecode: &Bytecode) -> fmt::Result {
+    write_listing(
+        output,
+        "main",
+        &bytecode.instructions,
+        &bytecode.lines,
+        &bytecode.constants,
+    )?;
+
+    for (index, constant) in bytecode.constants.iter().enumerate() {
+        if let Object::CompiledFunction(function) = constant {
+            writeln!(output)?;
+            write_listing(
+                output,
+                &format!("constant {}: {}", index, function),
+                &function.instructions,
+                &function.lines,
+                &bytecode.constants,
+            )?;
+        }
+    }
+
+    return Ok(());
+}
+
+fn write_listing(
+    output: &mut String,
+    name: &str,
+    instructions: &[u8],
+    lines: &[(usize, usize)],
+    constants: &[Object],
+) -> fmt::Result {
+    writeln!(output, "== {} ==", name)?;
+
+    let mut offset = 0;
+    let mut previous_line = None;
+
+    while offset < instructions.len() {
+        // The compiler records lines in instruction order.
+        let index = lines.partition_point(|(position, _)| *position < offset);
+        let line = lines
+            .get(index)
+            .filter(|(position, _)| *position == offset)
+            .map(|(_, line)| *line);
+
+        match line {
+            Some(line) if previous_line != Some(line) => {
+                write!(output, "{:04} {:>4} ", offset, line)?
+            }
+            _ => write!(output, "{:04}    | ", offset)?,
+        }
+
+        if line.is_some() {
+            previous_line = line;
+        }
+
+        let Ok(op) = Opcode::try_from(instructions[offset]) else {
+            writeln!(output, "unknown opcode {}", instructions[offset])?;
+            offset += 1;
+            continue;
+        };
+
+        let operands = read_operands(op, &instructions[offset + 1..]);
+        write!(output, "{}", op.name())?;
+
+        for operand in &operands {
+            write!(output, " {}", operand)?;
+        }
+
+        if op == Opcode::Constant || op == Opcode::Closure {
+            match constants.get(operands[0]) {
+                Some(Object::String(value)) => write!(output, " ({:?})", value)?,
+                Some(constant) => write!(output, " ({})", constant)?,
+                None => write!(output, " (missing)")?,
+            }
+        }
+
+        writeln!(output)?;
+        offset += op.width();
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{disassemble, disassemble_source};
+    use crate::{
+        code::{make, Opcode},
+        compiler::Bytecode,
+        interpreter::Error,
+        object::Object,
+    };
+
+    #[test]
+    fn test_disassemble_source() {
+        let input = "let a = \"hi\";\nif (a == \"hi\") {\n  1\n} else { 2 }";
+
+        let expected = "== main ==
+0000    1 OpConstant 0 (\"hi\")
+0003    | OpSetGlobal 0
+0006    2 OpGetGlobal 0
+0009    | OpConstant 1 (\"hi\")
+0012    | OpEqual
+0013    | OpJumpNotTruthy 22
+0016    3 OpConstant 2 (1)
+0019    2 OpJump 25
+0022    4 OpConstant 3 (2)
+0025    2 OpPop
+";
+
+        assert_eq!(disassemble_source(input), Ok(String::from(expected)));
+    }
+
+    #[test]
+    fn test_disassemble_functions() {
+        let input = "let f = fn(x) {\n  x + 1\n};\nf(2)";
+
+        let expected = "== main ==
+0000    1 OpClosure 1 (compiled fn/1)
+0003    | OpSetGlobal 0
+0006    4 OpGetGlobal 0
+0009    | OpConstant 2 (2)
+0012    | OpCall 1
+0014    | OpPop
+
+== constant 1: compiled fn/1 ==
+0000    2 OpGetLocal 0
+0002    | OpConstant 0 (1)
+0005    | OpAdd
+0006    1 OpReturnValue
+";
+
+        assert_eq!(disassemble_source(input), Ok(String::from(expected)));
+    }
+
+    #[test]
+    fn test_disassemble_without_lines() {
+        let bytecode = Bytecode {
+            instructions: [make(Opcode::Constant, &[0]), make(Opcode::Pop, &[])].concat(),
+            lines: Vec::new(),
+            constants: vec![Object::Integer(7)],
+            global_names: Vec::new(),
+        };
+
+        assert_eq!(
+            disassemble(&bytecode),
+            "== main ==\n0000    | OpConstant 0 (7)\n0003    | OpPop\n"
+        );
+    }
+
+    #[test]
+    fn test_disassemble_source_errors() {
+        assert!(matches!(
+            disassemble_source("let = 1;"),
+            Err(Error::Parse(_))
+        ));
+
+        let call = format!("f({})", vec!["1"; 256].join(", "));
+        assert_eq!(
+            disassemble_source(&call).unwrap_err().to_string(),
+            "too many call arguments, the limit is 255"
+        );
+    }
+}
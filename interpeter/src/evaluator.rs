@@ -0,0 +1,1130 @@
+use std::{cell::Cell, collections::HashMap, fmt, rc::Rc};
+
+use crate::{
+    ast::{BlockStatement, Expresion, Operator, Program, Statement},
+    builtins,
+    object::{Environment, Function, HashKey, Object},
+};
+
+#[derive(PartialEq, Debug)]
+pub enum RuntimeError {
+    UnknownIdentifier(String),
+    UndeclaredAssignment(String),
+    UnknownPrefixOperator {
+        operator: Operator,
+        operand: &'static str,
+    },
+    UnknownInfixOperator {
+        left: &'static str,
+        operator: Operator,
+        right: &'static str,
+    },
+    TypeMismatch {
+        left: &'static str,
+        operator: Operator,
+        right: &'static str,
+    },
+    IntegerOverflow,
+    DivisionByZero,
+    NegativeExponent(i64),
+    NotAFunction(&'static str),
+    WrongArgumentCount {
+        expected: usize,
+        got: usize,
+    },
+    UnexpectedType {
+        expected: &'static str,
+        got: &'static str,
+    },
+    UnsupportedArgument {
+        function: &'static str,
+        got: &'static str,
+    },
+    UnsupportedIndex {
+        left: &'static str,
+        index: &'static str,
+    },
+    IndexOutOfBounds {
+        index: i64,
+        length: usize,
+    },
+    UnhashableKey(&'static str),
+    NotIterable(&'static str),
+    /// Raised by a host function with its own message.
+    Custom(String),
+    StackOverflow,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::UnknownIdentifier(name) => write!(f, "identifier not found: {}", name),
+            RuntimeError::UndeclaredAssignment(name) => {
+                write!(f, "assignment to undeclared variable: {}", name)
+            }
+            RuntimeError::UnknownPrefixOperator { operator, operand } => {
+                write!(f, "unknown operator: {}{}", operator, operand)
+            }
+            RuntimeError::UnknownInfixOperator {
+                left,
+                operator,
+                right,
+            } => write!(f, "unknown operator: {} {} {}", left, operator, right),
+            RuntimeError::TypeMismatch {
+                left,
+                operator,
+                right,
+            } => write!(f, "type mismatch: {} {} {}", left, operator, right),
+            RuntimeError::IntegerOverflow => write!(f, "integer overflow"),
+            RuntimeError::DivisionByZero => write!(f, "division by zero"),
+            RuntimeError::NegativeExponent(exponent) => {
+                write!(f, "exponent must not be negative, got {}", exponent)
+            }
+            RuntimeError::NotAFunction(name) => write!(f, "not a function: {}", name),
+            RuntimeError::WrongArgumentCount { expected, got } => {
+                write!(
+                    f,
+                    "wrong number of arguments: expected {}, got {}",
+                    expected, got
+                )
+            }
+            RuntimeError::UnexpectedType { expected, got } => {
+                write!(f, "expected {}, got {}", expected, got)
+            }
+            RuntimeError::UnsupportedArgument { function, got } => {
+                write!(f, "argument to `{}` not supported, got {}", function, got)
+            }
+            RuntimeError::UnsupportedIndex { left, index } => {
+                write!(f, "index operator not supported: {}[{}]", left, index)
+            }
+            RuntimeError::IndexOutOfBounds { index, length } => {
+                write!(
+                    f,
+                    "index {} out of bounds for array of length {}",
+                    index, length
+                )
+            }
+            RuntimeError::UnhashableKey(name) => {
+                write!(
+                    f,
+                    "unusable as hash key: {}, only INTEGER, STRING and BOOLEAN are",
+                    name
+                )
+            }
+            RuntimeError::NotIterable(name) => write!(f, "cannot loop over {}", name),
+            RuntimeError::Custom(message) => write!(f, "{}", message),
+            RuntimeError::StackOverflow => write!(f, "stack overflow"),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// Calls nested deeper than this are reported instead of growing forever.
+/// Shared with the VM.
+pub(crate) const MAX_CALL_DEPTH: usize = 1024;
+
+thread_local! {
+    /// Function calls the evaluator is in the middle of.
+    static CALL_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Why evaluation stopped before the end of an expression: an error, or a
+/// `return`, `break` or `continue` on its way out to the function or loop
+/// it belongs to. Keeping them on the `Err` side lets `?` carry them out of
+/// any expression they appear in.
+enum Unwind {
+    Error(RuntimeError),
+    Return(Object),
+    Break,
+    Continue,
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(value: RuntimeError) -> Self {
+        return Unwind::Error(value);
+    }
+}
+
+pub fn eval(program: &Program, env: &mut Environment) -> Result<Object, RuntimeError> {
+    let mut result = Object::Null;
+
+    for statement in &program.statements {
+        result = match eval_statement(statement, env) {
+            Ok(value) => value,
+            Err(Unwind::Return(value)) => return Ok(value),
+            Err(Unwind::Error(error)) => return Err(error),
+            Err(Unwind::Break | Unwind::Continue) => {
+                unreachable!("Parser produced break or continue outside of a loop")
+            }
+        };
+    }
+
+    return Ok(result);
+}
+
+// The evaluator recurses on the native stack, a few frames for every call
+// in the script. `eval_statement` and `eval_expression` only dispatch so
+// their frames stay small, the work happens in a function per node.
+
+fn eval_statement(statement: &Statement, env: &mut Environment) -> Result<Object, Unwind> {
+    match statement {
+        Statement::Let {
+            name: Expresion::Identifer(name, _),
+            value,
+            ..
+        } => return eval_let(name, value, env),
+        Statement::Let { name, .. } => {
+            unreachable!(
+                "Parser produced let statement without identifier {:?}",
+                name
+            )
+        }
+        Statement::Return(expression, _) => {
+            return Err(Unwind::Return(eval_expression(expression, env)?))
+        }
+        Statement::Expression(expression, _) => return eval_expression(expression, env),
+        Statement::While {
+            condition, body, ..
+        } => return eval_while(condition, body, env),
+        Statement::For {
+            variable: Expresion::Identifer(name, _),
+            iterable,
+            body,
+            ..
+        } => return eval_for(name, iterable, body, env),
+        Statement::For { variable, .. } => {
+            unreachable!(
+                "Parser produced for statement without identifier {:?}",
+                variable
+            )
+        }
+        Statement::Break(_) => return Err(Unwind::Break),
+        Statement::Continue(_) => return Err(Unwind::Continue),
+    }
+}
+
+fn eval_let(name: &str, value: &Expresion, env: &mut Environment) -> Result<Object, Unwind> {
+    let value = eval_expression(value, env)?;
+    env.set(name.to_owned(), value);
+
+    return Ok(Object::Null);
+}
+
+fn eval_while(
+    condition: &Expresion,
+    body: &BlockStatement,
+    env: &mut Environment,
+) -> Result<Object, Unwind> {
+    while eval_expression(condition, env)?.is_truthy() {
+        match eval_block(body, env) {
+            Ok(_) | Err(Unwind::Continue) => {}
+            Err(Unwind::Break) => break,
+            Err(unwind) => return Err(unwind),
+        }
+    }
+
+    return Ok(Object::Null);
+}
+
+fn eval_for(
+    name: &str,
+    iterable: &Expresion,
+    body: &BlockStatement,
+    env: &mut Environment,
+) -> Result<Object, Unwind> {
+    let iterable = eval_expression(iterable, env)?;
+
+    for item in iterate(&iterable)?.iter() {
+        env.set(name.to_owned(), item.clone());
+
+        match eval_block(body, env) {
+            Ok(_) | Err(Unwind::Continue) => {}
+            Err(Unwind::Break) => break,
+            Err(unwind) => return Err(unwind),
+        }
+    }
+
+    return Ok(Object::Null);
+}
+
+fn eval_block(block: &BlockStatement, env: &mut Environment) -> Result<Object, Unwind> {
+    let mut result = Object::Null;
+
+    for statement in &block.statements {
+        result = eval_statement(statement, env)?;
+    }
+
+    return Ok(result);
+}
+
+fn eval_expression(expression: &Expresion, env: &mut Environment) -> Result<Object, Unwind> {
+    match expression {
+        Expresion::Identifer(name, _) => return eval_identifier(name, env),
+        Expresion::Interger(value, _) => return Ok(Object::Integer(*value)),
+        Expresion::String(value, _) => return Ok(Object::String(value.to_owned())),
+        Expresion::Bool { value, .. } => return Ok(Object::Boolean(*value)),
+        Expresion::Prefix(operator, right, _) => {
+            return eval_prefix_expression(*operator, right, env)
+        }
+        Expresion::Infix(left, operator @ (Operator::And | Operator::Or), right, _) => {
+            return eval_logical(left, *operator, right, env)
+        }
+        Expresion::Infix(left, operator, right, _) => {
+            return eval_infix_expression(left, *operator, right, env)
+        }
+        Expresion::If {
+            condition,
+            consequence,
+            alternative,
+            ..
+        } => return eval_if(condition, consequence, alternative.as_ref(), env),
+        Expresion::Function { params, body, .. } => {
+            let params = params.iter().map(|param| param.to_string()).collect();
+
+            return Ok(Object::Function(Rc::new(Function {
+                params,
+                body: Rc::clone(body),
+                env: env.clone(),
+            })));
+        }
+        Expresion::Call { function, args, .. } => return eval_call(function, args, env),
+        Expresion::Array(elements, _) => return eval_array(elements, env),
+        Expresion::Hash(pairs, _) => return eval_hash(pairs, env),
+        Expresion::Index { left, index, .. } => return eval_index_expression(left, index, env),
+        Expresion::Assign {
+            target,
+            operator,
+            value,
+            ..
+        } => return eval_assign(target, *operator, value, env),
+    }
+}
+
+fn eval_identifier(name: &str, env: &Environment) -> Result<Object, Unwind> {
+    match env.get(name).or_else(|| builtins::lookup(name)) {
+        Some(value) => return Ok(value),
+        None => return Err(RuntimeError::UnknownIdentifier(name.to_owned()).into()),
+    }
+}
+
+fn eval_prefix_expression(
+    operator: Operator,
+    right: &Expresion,
+    env: &mut Environment,
+) -> Result<Object, Unwind> {
+    let right = eval_expression(right, env)?;
+
+    return Ok(eval_prefix(operator, right)?);
+}
+
+/// `&&` and `||` only evaluate their right side when the left one does not
+/// already decide the result.
+fn eval_logical(
+    left: &Expresion,
+    operator: Operator,
+    right: &Expresion,
+    env: &mut Environment,
+) -> Result<Object, Unwind> {
+    let left = eval_expression(left, env)?.is_truthy();
+
+    if left == (operator == Operator::Or) {
+        return Ok(Object::Boolean(left));
+    }
+
+    return Ok(Object::Boolean(eval_expression(right, env)?.is_truthy()));
+}
+
+fn eval_infix_expression(
+    left: &Expresion,
+    operator: Operator,
+    right: &Expresion,
+    env: &mut Environment,
+) -> Result<Object, Unwind> {
+    let left = eval_expression(left, env)?;
+    let right = eval_expression(right, env)?;
+
+    return Ok(eval_infix(left, operator, right)?);
+}
+
+fn eval_if(
+    condition: &Expresion,
+    consequence: &BlockStatement,
+    alternative: Option<&BlockStatement>,
+    env: &mut Environment,
+) -> Result<Object, Unwind> {
+    if eval_expression(condition, env)?.is_truthy() {
+        return eval_block(consequence, env);
+    }
+
+    match alternative {
+        Some(alternative) => return eval_block(alternative, env),
+        None => return Ok(Object::Null),
+    }
+}
+
+fn eval_call(
+    function: &Expresion,
+    args: &[Expresion],
+    env: &mut Environment,
+) -> Result<Object, Unwind> {
+    let function = eval_expression(function, env)?;
+    let args = eval_expressions(args, env)?;
+
+    return Ok(apply_function(function, args)?);
+}
+
+fn eval_array(elements: &[Expresion], env: &mut Environment) -> Result<Object, Unwind> {
+    return Ok(Object::Array(Rc::new(eval_expressions(elements, env)?)));
+}
+
+fn eval_hash(pairs: &[(Expresion, Expresion)], env: &mut Environment) -> Result<Object, Unwind> {
+    let mut hash = HashMap::with_capacity(pairs.len());
+
+    for (key, value) in pairs {
+        let key = eval_expression(key, env)?;
+        let value = eval_expression(value, env)?;
+
+        hash.insert(hash_key(&key)?, value);
+    }
+
+    return Ok(Object::Hash(Rc::new(hash)));
+}
+
+fn eval_index_expression(
+    left: &Expresion,
+    index: &Expresion,
+    env: &mut Environment,
+) -> Result<Object, Unwind> {
+    let left = eval_expression(left, env)?;
+    let index = eval_expression(index, env)?;
+
+    return Ok(eval_index(&left, &index)?);
+}
+
+/// Evaluates `expressions` left to right.
+fn eval_expressions<'a>(
+    expressions: impl IntoIterator<Item = &'a Expresion>,
+    env: &mut Environment,
+) -> Result<Vec<Object>, Unwind> {
+    return expressions
+        .into_iter()
+        .map(|expression| eval_expression(expression, env))
+        .collect();
+}
+
+/// Checks the variable being assigned to exists, evaluates the indexes into
+/// it left to right and then the value, and updates the variable in place.
+fn eval_assign(
+    target: &Expresion,
+    operator: Option<Operator>,
+    value: &Expresion,
+    env: &mut Environment,
+) -> Result<Object, Unwind> {
+    let mut indexes = Vec::new();
+    let mut place = target;
+
+    while let Expresion::Index { left, index, .. } = place {
+        indexes.push(index.as_ref());
+        place = left;
+    }
+
+    let Expresion::Identifer(name, _) = place else {
+        unreachable!("Parser produced assignment to {:?}", place);
+    };
+
+    if env.get(name).is_none() {
+        return Err(RuntimeError::UndeclaredAssignment(name.to_owned()).into());
+    }
+
+    let indexes = eval_expressions(indexes.into_iter().rev(), env)?;
+    let value = eval_expression(value, env)?;
+
+    // Updating the binding where it is, rather than a copy of it, leaves
+    // its container unshared so `Rc::make_mut` does not clone it.
+    let value = env
+        .update(name, |base| {
+            eval_assign_index(base, &indexes, operator, value)
+        })
+        .expect("checked above");
+
+    return Ok(value?);
+}
+
+/// Stores `value` at the end of `indexes` into `base`, combined with what is
+/// there first for compound assignment, and returns the value stored.
+/// Containers are changed in place, unless other bindings share them, then
+/// they are copied rather than changed under them. `base` keeps its value
+/// when this fails. Shared with the VM.
+pub(crate) fn eval_assign_index(
+    base: &mut Object,
+    indexes: &[Object],
+    operator: Option<Operator>,
+    value: Object,
+) -> Result<Object, RuntimeError> {
+    let Some((index, rest)) = indexes.split_first() else {
+        let value = match operator {
+            Some(operator) => eval_infix(base.clone(), operator, value)?,
+            None => value,
+        };
+
+        *base = value.clone();
+
+        return Ok(value);
+    };
+
+    match (base, index) {
+        (Object::Array(elements), Object::Integer(index)) => {
+            let position = array_position(elements, *index)?;
+            let current = &mut Rc::make_mut(elements)[position];
+
+            return eval_assign_index(current, rest, operator, value);
+        }
+        (Object::Hash(pairs), _) => {
+            let key = hash_key(index)?;
+            let pairs = Rc::make_mut(pairs);
+
+            if let Some(current) = pairs.get_mut(&key) {
+                return eval_assign_index(current, rest, operator, value);
+            }
+
+            // A missing key reads as null, like in `eval_index`, and is
+            // only added once the assignment succeeded.
+            let mut current = Object::Null;
+            let value = eval_assign_index(&mut current, rest, operator, value)?;
+            pairs.insert(key, current);
+
+            return Ok(value);
+        }
+        (base, _) => {
+            return Err(RuntimeError::UnsupportedIndex {
+                left: base.type_name(),
+                index: index.type_name(),
+            })
+        }
+    }
+}
+
+/// What a `for` loop visits: the elements of an array, the characters of a
+/// string or the keys of a hash in the order they are printed. Shared with
+/// the VM.
+pub(crate) fn iterate(iterable: &Object) -> Result<Rc<Vec<Object>>, RuntimeError> {
+    match iterable {
+        Object::Array(elements) => return Ok(Rc::clone(elements)),
+        Object::String(value) => {
+            let chars = value.chars().map(|c| Object::String(c.to_string()));
+
+            return Ok(Rc::new(chars.collect()));
+        }
+        Object::Hash(pairs) => {
+            let mut keys: Vec<&HashKey> = pairs.keys().collect();
+            keys.sort();
+
+            let keys = keys.into_iter().map(|key| Object::from(key.clone()));
+
+            return Ok(Rc::new(keys.collect()));
+        }
+        _ => return Err(RuntimeError::NotIterable(iterable.type_name())),
+    }
+}
+
+/// Only integers, strings and booleans can key a hash. Shared with the VM.
+pub(crate) fn hash_key(key: &Object) -> Result<HashKey, RuntimeError> {
+    return key
+        .hash_key()
+        .ok_or(RuntimeError::UnhashableKey(key.type_name()));
+}
+
+/// Negative indexes count back from the end of the array, `-1` being the
+/// last element. Missing hash keys give null. Shared with the VM.
+pub(crate) fn eval_index(left: &Object, index: &Object) -> Result<Object, RuntimeError> {
+    match (left, index) {
+        (Object::Array(elements), Object::Integer(index)) => {
+            return Ok(elements[array_position(elements, *index)?].clone());
+        }
+        (Object::Hash(pairs), _) => {
+            return Ok(pairs
+                .get(&hash_key(index)?)
+                .cloned()
+                .unwrap_or(Object::Null));
+        }
+        _ => {
+            return Err(RuntimeError::UnsupportedIndex {
+                left: left.type_name(),
+                index: index.type_name(),
+            })
+        }
+    }
+}
+
+/// Where `index` points into `elements`, counting from the end when it is
+/// negative.
+fn array_position(elements: &[Object], index: i64) -> Result<usize, RuntimeError> {
+    let position = match index < 0 {
+        true => index.checked_add(elements.len() as i64),
+        false => Some(index),
+    };
+
+    match position.and_then(|position| usize::try_from(position).ok()) {
+        Some(position) if position < elements.len() => return Ok(position),
+        _ => {
+            return Err(RuntimeError::IndexOutOfBounds {
+                index,
+                length: elements.len(),
+            })
+        }
+    }
+}
+
+/// Calls `function` in a new scope enclosed by the environment the function
+/// was defined in, with each parameter bound to its argument.
+fn apply_function(function: Object, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    let function = match function {
+        Object::Function(function) => function,
+        Object::Host(host) => return (host.func)(&args).map_err(RuntimeError::from),
+        _ => return Err(RuntimeError::NotAFunction(function.type_name())),
+    };
+
+    if function.params.len() != args.len() {
+        return Err(RuntimeError::WrongArgumentCount {
+            expected: function.params.len(),
+            got: args.len(),
+        });
+    }
+
+    let depth = CALL_DEPTH.with(Cell::get);
+
+    if depth >= MAX_CALL_DEPTH {
+        return Err(RuntimeError::StackOverflow);
+    }
+
+    let mut env = Environment::new_enclosed(&function.env);
+
+    for (param, arg) in function.params.iter().zip(args) {
+        env.set(param.to_owned(), arg);
+    }
+
+    CALL_DEPTH.with(|calls| calls.set(depth + 1));
+    let result = eval_block(&function.body, &mut env);
+    CALL_DEPTH.with(|calls| calls.set(depth));
+
+    match result {
+        Ok(value) | Err(Unwind::Return(value)) => return Ok(value),
+        Err(Unwind::Error(error)) => return Err(error),
+        Err(Unwind::Break | Unwind::Continue) => {
+            unreachable!("Parser let break or continue escape a function body")
+        }
+    }
+}
+
+/// Shared with the VM so both backends agree on what operators do.
+pub(crate) fn eval_prefix(operator: Operator, right: Object) -> Result<Object, RuntimeError> {
+    match (operator, &right) {
+        (Operator::Not, _) => return Ok(Object::Boolean(!right.is_truthy())),
+        (Operator::Minus, Object::Integer(value)) => {
+            return Ok(Object::Integer(checked(value.checked_neg())?))
+        }
+        _ => {
+            return Err(RuntimeError::UnknownPrefixOperator {
+                operator,
+                operand: right.type_name(),
+            })
+        }
+    }
+}
+
+/// Shared with the VM so both backends agree on what operators do.
+pub(crate) fn eval_infix(
+    left: Object,
+    operator: Operator,
+    right: Object,
+) -> Result<Object, RuntimeError> {
+    match (&left, &right) {
+        (Object::Integer(left), Object::Integer(right)) => {
+            return eval_integer_infix(*left, operator, *right)
+        }
+        (Object::String(left), Object::String(right)) => match operator {
+            Operator::Plus => return Ok(Object::String(format!("{}{}", left, right))),
+            Operator::Equals => return Ok(Object::Boolean(left == right)),
+            Operator::NotEquals => return Ok(Object::Boolean(left != right)),
+            _ => {}
+        },
+        (Object::Boolean(left), Object::Boolean(right)) => match operator {
+            Operator::Equals => return Ok(Object::Boolean(left == right)),
+            Operator::NotEquals => return Ok(Object::Boolean(left != right)),
+            _ => {}
+        },
+        _ if left.type_name() != right.type_name() => {
+            return Err(RuntimeError::TypeMismatch {
+                left: left.type_name(),
+                operator,
+                right: right.type_name(),
+            })
+        }
+        _ => {}
+    }
+
+    return Err(RuntimeError::UnknownInfixOperator {
+        left: left.type_name(),
+        operator,
+        right: right.type_name(),
+    });
+}
+
+fn eval_integer_infix(left: i64, operator: Operator, right: i64) -> Result<Object, RuntimeError> {
+    let result = match operator {
+        Operator::Plus => Object::Integer(checked(left.checked_add(right))?),
+        Operator::Minus => Object::Integer(checked(left.checked_sub(right))?),
+        Operator::Multiply => Object::Integer(checked(left.checked_mul(right))?),
+        Operator::Divide => {
+            if right == 0 {
+                return Err(RuntimeError::DivisionByZero);
+            }
+
+            Object::Integer(checked(left.checked_div(right))?)
+        }
+        // Like division this truncates, so the result has the sign of `left`,
+        // and `i64::MIN % -1` overflows just as `i64::MIN / -1` does.
+        Operator::Modulo => {
+            if right == 0 {
+                return Err(RuntimeError::DivisionByZero);
+            }
+
+            Object::Integer(checked(left.checked_rem(right))?)
+        }
+        Operator::Power => Object::Integer(integer_power(left, right)?),
+        Operator::Gt => Object::Boolean(left > right),
+        Operator::Lt => Object::Boolean(left < right),
+        Operator::GtEq => Object::Boolean(left >= right),
+        Operator::LtEq => Object::Boolean(left <= right),
+        Operator::Equals => Object::Boolean(left == right),
+        Operator::NotEquals => Object::Boolean(left != right),
+        Operator::Not | Operator::And | Operator::Or => {
+            return Err(RuntimeError::UnknownInfixOperator {
+                left: "INTEGER",
+                operator,
+                right: "INTEGER",
+            })
+        }
+    };
+
+    return Ok(result);
+}
+
+fn checked(result: Option<i64>) -> Result<i64, RuntimeError> {
+    return result.ok_or(RuntimeError::IntegerOverflow);
+}
+
+fn integer_power(base: i64, exponent: i64) -> Result<i64, RuntimeError> {
+    if exponent < 0 {
+        return Err(RuntimeError::NegativeExponent(exponent));
+    }
+
+    match (base, u32::try_from(exponent)) {
+        (_, Ok(exponent)) => return checked(base.checked_pow(exponent)),
+        // Only these stay in range for exponents that do not fit a u32.
+        (0 | 1, Err(_)) => return Ok(base),
+        (-1, Err(_)) => return Ok(if exponent % 2 == 0 { 1 } else { -1 }),
+        (_, Err(_)) => return Err(RuntimeError::IntegerOverflow),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::{eval, RuntimeError};
+    use crate::{
+        ast::Operator,
+        corpus,
+        object::{Environment, Object},
+        parser::Parser,
+    };
+
+    fn eval_input(input: &str) -> Result<Object, RuntimeError> {
+        let mut parser = Parser::new(input);
+        let program = parser.parse_program();
+
+        assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+
+        return eval(&program, &mut Environment::new());
+    }
+
+    #[test]
+    fn test_eval_corpus() {
+        // The recursion cases go deeper than the stack of a test thread
+        // allows in a debug build.
+        let runner = thread::Builder::new().stack_size(64 * 1024 * 1024);
+
+        let corpus = runner.spawn(|| {
+            for (input, expected) in corpus::CASES {
+                let result = match eval_input(input) {
+                    Ok(value) => value.to_string(),
+                    Err(error) => format!("error: {}", error),
+                };
+
+                assert_eq!(result, *expected, "{}", input);
+            }
+        });
+
+        if let Err(panic) = corpus.unwrap().join() {
+            std::panic::resume_unwind(panic);
+        }
+    }
+
+    #[test]
+    fn test_eval_integer_expression() {
+        let tests = vec![
+            ("5", 5),
+            ("10", 10),
+            ("-5", -5),
+            ("-10", -10),
+            ("5 + 5 + 5 + 5 - 10", 10),
+            ("2 * 2 * 2 * 2 * 2", 32),
+            ("-50 + 100 + -50", 0),
+            ("5 * 2 + 10", 20),
+            ("5 + 2 * 10", 25),
+            ("20 + 2 * -10", 0),
+            ("50 / 2 * 2 + 10", 60),
+            ("-7 / 2", -3),
+            ("2 * (5 + 10)", 30),
+            ("3 * 3 * 3 + 10", 37),
+            ("3 * (3 * 3) + 10", 37),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", 50),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval_input(input),
+                Ok(Object::Integer(expected)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_boolean_expression() {
+        let tests = vec![
+            ("true", true),
+            ("false", false),
+            ("1 < 2", true),
+            ("1 > 2", false),
+            ("1 < 1", false),
+            ("1 == 1", true),
+            ("1 != 1", false),
+            ("1 == 2", false),
+            ("1 != 2", true),
+            ("true == true", true),
+            ("false == false", true),
+            ("true == false", false),
+            ("true != false", true),
+            ("1 < 2 == true", true),
+            ("1 > 2 == true", false),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval_input(input),
+                Ok(Object::Boolean(expected)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_bang_operator() {
+        let tests = vec![
+            ("!true", false),
+            ("!false", true),
+            ("!5", false),
+            ("!!true", true),
+            ("!!false", false),
+            ("!!5", true),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval_input(input),
+                Ok(Object::Boolean(expected)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_return_statement() {
+        let tests = vec![
+            ("return 10;", 10),
+            ("return 10; 9;", 10),
+            ("return 2 * 5; 9;", 10),
+            ("9; return 2 * 5; 9;", 10),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval_input(input),
+                Ok(Object::Integer(expected)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_if_else_expression() {
+        let tests = vec![
+            ("if (true) { 10 }", Object::Integer(10)),
+            ("if (false) { 10 }", Object::Null),
+            ("if (1) { 10 }", Object::Integer(10)),
+            ("if (1 < 2) { 10 }", Object::Integer(10)),
+            ("if (1 > 2) { 10 }", Object::Null),
+            ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
+            ("if (1 < 2) { 10 } else { 20 }", Object::Integer(10)),
+            (
+                "if (1 > 2) { 10 } else if (2 > 1) { 15 } else { 20 }",
+                Object::Integer(15),
+            ),
+            (
+                "if (1 > 2) { 10 } else if (2 > 3) { 15 } else { 20 }",
+                Object::Integer(20),
+            ),
+            ("if (true) { }", Object::Null),
+            (
+                "let x = if (false) { 1 } else { 2 }; x * 3",
+                Object::Integer(6),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval_input(input), Ok(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_nested_return() {
+        let input = "
+        if (10 > 1) {
+            if (10 > 1) {
+                return 10;
+            }
+
+            return 1;
+        }";
+
+        assert_eq!(eval_input(input), Ok(Object::Integer(10)));
+    }
+
+    #[test]
+    fn test_eval_let_statement() {
+        let tests = vec![
+            ("let a = 5; a;", 5),
+            ("let a = 5 * 5; a;", 25),
+            ("let a = 5; let b = a; b;", 5),
+            ("let a = 5; let b = a; let c = a + b + 5; c;", 15),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval_input(input),
+                Ok(Object::Integer(expected)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_let_statement_evaluates_to_null() {
+        assert_eq!(eval_input("let a = 5;"), Ok(Object::Null));
+    }
+
+    #[test]
+    fn test_eval_string_expression() {
+        let tests = vec![
+            (r#""Hello World!""#, "Hello World!"),
+            (r#""Hello" + " " + "World!""#, "Hello World!"),
+            (r#"let key = "user"; key + "\t" + "42""#, "user\t42"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval_input(input),
+                Ok(Object::String(String::from(expected))),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_string_comparison() {
+        let tests = vec![
+            (r#""a" == "a""#, true),
+            (r#""a" == "b""#, false),
+            (r#""a" != "b""#, true),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval_input(input),
+                Ok(Object::Boolean(expected)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        let tests = vec![
+            (
+                "5 + true;",
+                RuntimeError::TypeMismatch {
+                    left: "INTEGER",
+                    operator: Operator::Plus,
+                    right: "BOOLEAN",
+                },
+            ),
+            (
+                "5 + true; 5;",
+                RuntimeError::TypeMismatch {
+                    left: "INTEGER",
+                    operator: Operator::Plus,
+                    right: "BOOLEAN",
+                },
+            ),
+            (
+                "-true",
+                RuntimeError::UnknownPrefixOperator {
+                    operator: Operator::Minus,
+                    operand: "BOOLEAN",
+                },
+            ),
+            (
+                "true + false;",
+                RuntimeError::UnknownInfixOperator {
+                    left: "BOOLEAN",
+                    operator: Operator::Plus,
+                    right: "BOOLEAN",
+                },
+            ),
+            (
+                "5; true > false; 5",
+                RuntimeError::UnknownInfixOperator {
+                    left: "BOOLEAN",
+                    operator: Operator::Gt,
+                    right: "BOOLEAN",
+                },
+            ),
+            (
+                "foobar",
+                RuntimeError::UnknownIdentifier(String::from("foobar")),
+            ),
+            ("10 / 0", RuntimeError::DivisionByZero),
+            (
+                r#""Hello" - "World""#,
+                RuntimeError::UnknownInfixOperator {
+                    left: "STRING",
+                    operator: Operator::Minus,
+                    right: "STRING",
+                },
+            ),
+            (
+                r#""Hello" + 1"#,
+                RuntimeError::TypeMismatch {
+                    left: "STRING",
+                    operator: Operator::Plus,
+                    right: "INTEGER",
+                },
+            ),
+            ("5(1)", RuntimeError::NotAFunction("INTEGER")),
+            (
+                "let add = fn(a, b) { a + b }; add(1)",
+                RuntimeError::WrongArgumentCount {
+                    expected: 2,
+                    got: 1,
+                },
+            ),
+            (
+                "let f = fn() { y }; let g = fn(y) { f() }; g(1)",
+                RuntimeError::UnknownIdentifier(String::from("y")),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(eval_input(input), Err(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval_function_object() {
+        let Ok(Object::Function(function)) = eval_input("fn(x) { x + 2; };") else {
+            panic!("Expected function object");
+        };
+
+        assert_eq!(function.params, vec![String::from("x")]);
+        assert_eq!(function.body.to_string(), "{ (x + 2) }");
+    }
+
+    #[test]
+    fn test_eval_function_application() {
+        let tests = vec![
+            ("let identity = fn(x) { x; }; identity(5);", 5),
+            ("let identity = fn(x) { return x; }; identity(5);", 5),
+            ("let double = fn(x) { x * 2; }; double(5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5, 5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));", 20),
+            ("fn(x) { x; }(5)", 5),
+            ("let f = fn() { return 1; 2 }; f() + 10", 11),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                eval_input(input),
+                Ok(Object::Integer(expected)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_closures() {
+        let input = "
+        let newAdder = fn(x) {
+            fn(y) { x + y };
+        };
+
+        let addTwo = newAdder(2);
+        addTwo(3);";
+
+        assert_eq!(eval_input(input), Ok(Object::Integer(5)));
+    }
+
+    #[test]
+    fn test_eval_recursive_function() {
+        let input = "
+        let fib = fn(n) {
+            if (n < 2) { return n; }
+            fib(n - 1) + fib(n - 2)
+        };
+
+        fib(15);";
+
+        assert_eq!(eval_input(input), Ok(Object::Integer(610)));
+    }
+
+    #[test]
+    fn test_eval_function_scope_does_not_leak() {
+        let input = "let x = 1; let f = fn(x) { let y = x; y }; f(2); x";
+
+        assert_eq!(eval_input(input), Ok(Object::Integer(1)));
+        assert_eq!(
+            eval_input("let f = fn(x) { let y = x; y }; f(2); y"),
+            Err(RuntimeError::UnknownIdentifier(String::from("y")))
+        );
+    }
+}
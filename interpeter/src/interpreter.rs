@@ -0,0 +1,440 @@
+use std::{cell::RefCell, fmt, io::Write, rc::Rc};
+
+use crate::{
+    ast::Program,
+    builtins,
+    compiler::{Bytecode, CompileError, Compiler},
+    diagnostic,
+    evaluator::{eval, RuntimeError},
+    object::{Environment, HostFunction, Object, Value},
+    parser::{ParseError, Parser},
+};
+
+/// Anything that can go wrong running a piece of source code.
+#[derive(PartialEq, Debug)]
+pub enum Error {
+    /// Every error the parser found, nothing was evaluated.
+    Parse(Vec<ParseError>),
+    /// Only produced when going through the bytecode compiler.
+    Compile(CompileError),
+    Runtime(RuntimeError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(errors) => {
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+
+                    write!(f, "{}", error)?;
+                }
+
+                Ok(())
+            }
+            Error::Compile(error) => write!(f, "{}", error),
+            Error::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    /// Error with a free form message, for host functions to report
+    /// failures of their own.
+    pub fn custom(message: impl Into<String>) -> Self {
+        return Error::Runtime(RuntimeError::Custom(message.into()));
+    }
+
+    /// Formats the error for a terminal. Parse errors point into `source`,
+    /// which must be the text that produced them.
+    pub fn render(&self, source: &str) -> String {
+        match self {
+            Error::Parse(errors) => {
+                return errors
+                    .iter()
+                    .map(|error| diagnostic::render(source, error))
+                    .collect();
+            }
+            _ => return format!("error: {}\n", self),
+        }
+    }
+}
+
+impl From<CompileError> for Error {
+    fn from(value: CompileError) -> Self {
+        return Error::Compile(value);
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(value: RuntimeError) -> Self {
+        return Error::Runtime(value);
+    }
+}
+
+/// Lets a host function's error surface as an ordinary script error.
+impl From<Error> for RuntimeError {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::Runtime(error) => return error,
+            error @ (Error::Parse(_) | Error::Compile(_)) => {
+                return RuntimeError::Custom(error.to_string())
+            }
+        }
+    }
+}
+
+/// Entry point for embedding the language. Bindings made by one call to
+/// `eval` stay visible to the next.
+///
+/// Scripts are evaluated on the native stack. Calls nested more than 1024
+/// deep fail with [`RuntimeError::StackOverflow`], but getting there can
+/// take more than the 8 MiB of a main thread in a debug build, so run
+/// deeply recursive scripts on a thread with a larger stack. Expressions
+/// nested more than [`MAX_NESTING`](crate::parser::MAX_NESTING) deep are
+/// rejected by the parser.
+///
+/// ```
+/// use interpeter::{Interpreter, Object};
+///
+/// let mut interpreter = Interpreter::new();
+/// interpreter.eval("let double = fn(x) { x * 2 };").unwrap();
+///
+/// assert_eq!(interpreter.eval("double(21)"), Ok(Object::Integer(42)));
+/// ```
+#[derive(Default)]
+pub struct Interpreter {
+    env: Environment,
+    /// Everything passed to `register_fn`, for `compile` to hand on.
+    host_functions: Vec<(String, Object)>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        return Interpreter::default();
+    }
+
+    /// Interpreter whose `puts` writes to `output` instead of stdout, in
+    /// both [`eval`](Self::eval) and bytecode from [`compile`](Self::compile).
+    ///
+    /// ```
+    /// use std::{cell::RefCell, rc::Rc};
+    /// use interpeter::Interpreter;
+    ///
+    /// let output = Rc::new(RefCell::new(Vec::new()));
+    /// let mut interpreter = Interpreter::with_output(output.clone());
+    /// interpreter.eval("puts(1, \"two\")").unwrap();
+    ///
+    /// assert_eq!(output.borrow().as_slice(), b"1\ntwo\n");
+    /// ```
+    pub fn with_output<W: Write + 'static>(output: Rc<RefCell<W>>) -> Self {
+        let mut interpreter = Interpreter::new();
+
+        // Shadows the builtin, just like a `let puts` in a script would.
+        interpreter.register_fn("puts", move |args: &[Value]| {
+            return builtins::puts_to(&mut *output.borrow_mut(), args);
+        });
+
+        return interpreter;
+    }
+
+    /// Parses and evaluates `source`, returning the value of its last statement.
+    pub fn eval(&mut self, source: &str) -> Result<Object, Error> {
+        let program = parse(source)?;
+
+        return Ok(self.eval_program(&program)?);
+    }
+
+    /// Parses and compiles `source` for the [`Vm`](crate::vm::Vm), with
+    /// the functions from [`register_fn`](Self::register_fn) defined as
+    /// globals. Bindings made by [`eval`](Self::eval) are not carried over.
+    ///
+    /// ```
+    /// use interpeter::{vm::Vm, Interpreter, Value};
+    ///
+    /// let mut interpreter = Interpreter::new();
+    /// interpreter.register_fn("answer", |_: &[Value]| Ok(Value::Integer(42)));
+    ///
+    /// let bytecode = interpreter.compile("answer() * 2").unwrap();
+    /// assert_eq!(Vm::new(bytecode).run(), Ok(Value::Integer(84)));
+    /// ```
+    pub fn compile(&self, source: &str) -> Result<Bytecode, Error> {
+        let program = parse(source)?;
+        let mut compiler = Compiler::new();
+
+        for (name, function) in &self.host_functions {
+            compiler.define_global(name, function.clone())?;
+        }
+
+        return Ok(compiler.compile(&program)?);
+    }
+
+    /// Makes `func` callable from scripts as `name`. Use
+    /// [`FromArgs`](crate::convert::FromArgs) to check the arguments.
+    ///
+    /// ```
+    /// use interpeter::{convert::FromArgs, Interpreter, Value};
+    ///
+    /// let mut interpreter = Interpreter::new();
+    /// interpreter.register_fn("max", |args: &[Value]| {
+    ///     let (a, b) = <(i64, i64)>::from_args(args)?;
+    ///     Ok(Value::Integer(a.max(b)))
+    /// });
+    ///
+    /// assert_eq!(interpreter.eval("max(3, 7)"), Ok(Value::Integer(7)));
+    /// ```
+    pub fn register_fn<F>(&mut self, name: &str, func: F)
+    where
+        F: Fn(&[Value]) -> Result<Value, Error> + 'static,
+    {
+        let function = HostFunction {
+            name: name.to_owned(),
+            func: Box::new(func),
+        };
+
+        let function = Object::Host(Rc::new(function));
+
+        self.env.set(name.to_owned(), function.clone());
+        self.host_functions.push((name.to_owned(), function));
+    }
+
+    /// Evaluates an already parsed program, for callers that want to look
+    /// at the AST or report parse errors themselves.
+    pub fn eval_program(&mut self, program: &Program) -> Result<Object, RuntimeError> {
+        return eval(program, &mut self.env);
+    }
+}
+
+/// Parses `source`, failing with every error the parser found.
+pub(crate) fn parse(source: &str) -> Result<Program, Error> {
+    let mut parser = Parser::new(source);
+    let program = parser.parse_program();
+
+    if !parser.errors().is_empty() {
+        return Err(Error::Parse(parser.errors().to_vec()));
+    }
+
+    return Ok(program);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+    use super::{Error, Interpreter};
+    use crate::{
+        convert::{FromArgs, IntoValue},
+        evaluator::RuntimeError,
+        object::{Object, Value},
+        parser::ParseError,
+        span::Span,
+        token::TokenKind,
+        vm::Vm,
+    };
+
+    #[test]
+    fn test_interpreter_keeps_bindings() {
+        let mut interpreter = Interpreter::new();
+
+        assert_eq!(interpreter.eval("let a = 2;"), Ok(Object::Null));
+        assert_eq!(interpreter.eval("let b = a * 3;"), Ok(Object::Null));
+        assert_eq!(interpreter.eval("a + b"), Ok(Object::Integer(8)));
+    }
+
+    #[test]
+    fn test_interpreter_errors() {
+        let mut interpreter = Interpreter::new();
+
+        assert_eq!(
+            interpreter.eval("let x 5; y"),
+            Err(Error::Parse(vec![ParseError::MissingAssign {
+                found: TokenKind::INT,
+                span: Span::new(6, 7, 1, 7),
+            }]))
+        );
+        assert_eq!(
+            interpreter.eval("y"),
+            Err(Error::Runtime(RuntimeError::UnknownIdentifier(
+                String::from("y")
+            )))
+        );
+    }
+
+    fn host_interpreter() -> Interpreter {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.register_fn("add", |args: &[Value]| {
+            let (a, b) = <(i64, i64)>::from_args(args)?;
+            Ok(Value::Integer(a + b))
+        });
+        interpreter.register_fn("sum", |args: &[Value]| {
+            let (numbers,) = <(Vec<i64>,)>::from_args(args)?;
+            Ok(numbers.iter().sum::<i64>().into_value())
+        });
+        interpreter.register_fn("config", |args: &[Value]| {
+            <()>::from_args(args)?;
+            Ok(HashMap::from([("debug", false), ("verbose", true)]).into_value())
+        });
+        interpreter.register_fn("fail", |_: &[Value]| Err(Error::custom("host failure")));
+
+        return interpreter;
+    }
+
+    #[test]
+    fn test_register_fn() {
+        let mut interpreter = host_interpreter();
+
+        assert_eq!(interpreter.eval("add(1, 2)"), Ok(Object::Integer(3)));
+        assert_eq!(
+            interpreter.eval("let twice = fn(x) { add(x, x) }; twice(add(2, 3))"),
+            Ok(Object::Integer(10))
+        );
+        assert_eq!(
+            interpreter.eval("config()").unwrap().to_string(),
+            r#"{"debug": false, "verbose": true}"#
+        );
+        assert_eq!(interpreter.eval("add").unwrap().to_string(), "builtin add");
+    }
+
+    #[test]
+    fn test_register_fn_in_vm() {
+        let interpreter = host_interpreter();
+
+        let run = |source| {
+            let bytecode = interpreter.compile(source)?;
+            return Vm::new(bytecode).run().map_err(Error::from);
+        };
+
+        assert_eq!(run("add(1, 2)"), Ok(Object::Integer(3)));
+        assert_eq!(
+            run("let twice = fn(x) { add(x, x) }; twice(sum([2, 3]))"),
+            Ok(Object::Integer(10))
+        );
+        assert_eq!(
+            run("let add = fn(a, b) { a - b }; add(1, 2)"),
+            Ok(Object::Integer(-1))
+        );
+        assert_eq!(
+            run("add(1)"),
+            Err(Error::Runtime(RuntimeError::WrongArgumentCount {
+                expected: 2,
+                got: 1,
+            }))
+        );
+        assert_eq!(run("fail()").unwrap_err().to_string(), "host failure");
+    }
+
+    #[test]
+    fn test_index_assignment_does_not_copy() {
+        let mut interpreter = Interpreter::new();
+
+        // Where an array or hash is stored, to tell whether it was copied.
+        interpreter.register_fn("address", |args: &[Value]| match args {
+            [Value::Array(elements)] => Ok(Value::Integer(Rc::as_ptr(elements) as usize as i64)),
+            [Value::Hash(pairs)] => Ok(Value::Integer(Rc::as_ptr(pairs) as usize as i64)),
+            _ => Err(Error::custom("expected an array or hash")),
+        });
+
+        // Only one assignment runs between two checks. A copy is made while
+        // the original is still alive, so it cannot reuse its address.
+        let source = r#"
+            let xs = [1, 2, 3];
+            let m = {"ys": [4]};
+            let xs_before = address(xs);
+            let m_before = address(m);
+            let ys_before = address(m["ys"]);
+            xs[0] = 10;
+            let xs_after = address(xs);
+            m["ys"][0] *= 2;
+            let m_after = address(m);
+            let ys_after = address(m["ys"]);
+            xs[2] += 1;
+            m["z"] = 1;
+            let local = fn() {
+                let zs = [1];
+                let set = fn() { zs[0] = 5 };
+                let before = address(zs);
+                zs[0] = 2;
+                let after = address(zs);
+                set();
+                [before == after, after == address(zs)]
+            };
+            let a = [1];
+            let b = a;
+            b[0] = 2;
+            [
+                xs_before == xs_after,
+                xs_after == address(xs),
+                m_before == m_after,
+                m_after == address(m),
+                ys_before == ys_after,
+                local()[0],
+                local()[1],
+                address(a) == address(b)
+            ]
+        "#;
+
+        let expected = vec![true, true, true, true, true, true, true, false].into_value();
+
+        assert_eq!(interpreter.eval(source), Ok(expected.clone()));
+
+        let bytecode = interpreter.compile(source).unwrap();
+        assert_eq!(Vm::new(bytecode).run(), Ok(expected));
+    }
+
+    #[test]
+    fn test_puts_writes_to_output() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::with_output(output.clone());
+
+        assert_eq!(
+            interpreter.eval("puts(1, \"a\"); puts([true])"),
+            Ok(Object::Null)
+        );
+
+        let bytecode = interpreter
+            .compile("let f = fn(x) { puts(x * 2) }; f(21)")
+            .unwrap();
+        assert_eq!(Vm::new(bytecode).run(), Ok(Object::Null));
+
+        assert_eq!(
+            String::from_utf8(output.borrow().clone()),
+            Ok(String::from("1\na\n[true]\n42\n"))
+        );
+    }
+
+    #[test]
+    fn test_register_fn_errors() {
+        let mut interpreter = host_interpreter();
+
+        assert_eq!(
+            interpreter.eval("add(1)"),
+            Err(Error::Runtime(RuntimeError::WrongArgumentCount {
+                expected: 2,
+                got: 1,
+            }))
+        );
+        assert_eq!(
+            interpreter.eval(r#"add(1, "2")"#),
+            Err(Error::Runtime(RuntimeError::UnexpectedType {
+                expected: "INTEGER",
+                got: "STRING",
+            }))
+        );
+        assert_eq!(
+            interpreter.eval("sum(1)"),
+            Err(Error::Runtime(RuntimeError::UnexpectedType {
+                expected: "ARRAY",
+                got: "INTEGER",
+            }))
+        );
+        assert_eq!(
+            interpreter.eval("fail()").unwrap_err().to_string(),
+            "host failure"
+        );
+    }
+}
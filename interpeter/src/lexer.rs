@@ -1,170 +1,347 @@
-use crate::token::Token;
-use std::char;
+use crate::{
+    diagnostic::Diagnostic,
+    span::Span,
+    token::{Token, TokenKind},
+};
+use std::{char, fmt};
 
-pub struct Lexer {
-    input: String,
-    position: Option<u32>,
-    read_position: Option<u32>,
+#[derive(PartialEq, Debug, Clone)]
+pub enum LexError {
+    IllegalCharacter { found: char, span: Span },
+    UnterminatedString { span: Span },
+    UnterminatedComment { span: Span },
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexError::IllegalCharacter { found, .. } => {
+                write!(f, "illegal character `{}`", found)
+            }
+            LexError::UnterminatedString { .. } => write!(f, "unterminated string literal"),
+            LexError::UnterminatedComment { .. } => write!(f, "unterminated block comment"),
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
+
+impl Diagnostic for LexError {
+    fn span(&self) -> Span {
+        match self {
+            LexError::IllegalCharacter { span, .. }
+            | LexError::UnterminatedString { span }
+            | LexError::UnterminatedComment { span } => return *span,
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            LexError::UnterminatedString { .. } => {
+                return Some(String::from("add a closing `\"` to end the string"))
+            }
+            LexError::UnterminatedComment { .. } => {
+                return Some(String::from(
+                    "block comments nest, every `/*` needs a matching `*/`",
+                ))
+            }
+            LexError::IllegalCharacter {
+                found: found @ ('&' | '|'),
+                ..
+            } => return Some(format!("did you mean `{0}{0}`?", found)),
+            _ => return None,
+        }
+    }
+}
+
+/// Converts source text into tokens in a single pass. Positions are byte
+/// offsets into `input`, so slicing out literals is always on a char boundary.
+pub struct Lexer<'src> {
+    input: &'src str,
+    position: usize,
+    read_position: usize,
     ch: Option<char>,
+    line: usize,
+    column: usize,
+    errors: Vec<LexError>,
 }
 
-impl Lexer {
-    pub fn new(input: String) -> Self {
+impl<'src> Lexer<'src> {
+    pub fn new(input: &'src str) -> Self {
         let mut lexer = Self {
             input,
-            position: None,
-            read_position: None,
+            position: 0,
+            read_position: 0,
             ch: None,
+            line: 1,
+            column: 1,
+            errors: Vec::new(),
         };
         lexer.read_char();
         return lexer;
     }
 
     fn read_char(&mut self) {
-        if self.position.is_none() {
-            self.position = Some(0);
-            self.read_position = Some(0);
+        match self.ch {
+            Some('\n') => {
+                self.line += 1;
+                self.column = 1;
+            }
+            Some(_) => self.column += 1,
+            None => {}
         }
 
-        if let Some(read_position) = self.read_position {
-            if read_position >= self.input.len() as u32 {
-                self.ch = None;
-            } else {
-                let new_char = self.input.chars().nth(read_position as usize).unwrap();
-                self.ch = Some(new_char);
-                self.position = Some(read_position);
-                self.read_position = Some(read_position + 1)
-            }
+        self.position = self.read_position;
+        self.ch = self.input[self.read_position..].chars().next();
+
+        if let Some(ch) = self.ch {
+            self.read_position += ch.len_utf8();
         }
     }
 
     fn peek_char(&self) -> Option<char> {
-        if let Some(read_position) = self.read_position {
-            if read_position >= self.input.len() as u32 {
-                return None;
-            } else {
-                return Some(self.input.chars().nth(read_position as usize).unwrap());
+        return self.input[self.read_position..].chars().next();
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        loop {
+            match (self.ch, self.peek_char()) {
+                (Some(' ' | '\t' | '\n' | '\r'), _) => self.read_char(),
+                (Some('/'), Some('/')) if !self.at_doc_comment() => self.skip_line(),
+                (Some('/'), Some('*')) => self.skip_block_comment(),
+                _ => break,
             }
         }
+    }
+
+    /// `///` starts a doc comment, `////` and longer are plain comments.
+    fn at_doc_comment(&self) -> bool {
+        let rest = &self.input[self.position..];
 
-        return None;
+        return rest.starts_with("///") && !rest.starts_with("////");
     }
 
-    fn skip_whitespace(&mut self) {
-        while let Some(ch) = self.ch {
-            if ch == ' ' || ch == '\t' || ch == '\n' || ch == '\r' {
-                self.read_char();
-            } else {
-                break;
-            }
+    fn skip_line(&mut self) {
+        while !matches!(self.ch, Some('\n') | None) {
+            self.read_char();
         }
     }
 
-    fn read_integer(&mut self) -> Option<String> {
-        if let Some(start_position) = self.position {
-            while let Some('0'..='9') = self.ch {
-                self.read_char();
+    fn skip_block_comment(&mut self) {
+        let start = Span::new(self.position, self.position, self.line, self.column);
+        let mut depth = 0;
+
+        loop {
+            match (self.ch, self.peek_char()) {
+                (Some('/'), Some('*')) => {
+                    depth += 1;
+                    self.read_char();
+                }
+                (Some('*'), Some('/')) => {
+                    depth -= 1;
+                    self.read_char();
+
+                    if depth == 0 {
+                        self.read_char();
+                        return;
+                    }
+                }
+                (None, _) => {
+                    let span = Span {
+                        end: self.position,
+                        ..start
+                    };
+                    self.errors.push(LexError::UnterminatedComment { span });
+                    return;
+                }
+                _ => {}
             }
 
-            return Some(
-                self.input[start_position as usize..self.position.unwrap() as usize].to_string(),
-            );
-        };
+            self.read_char();
+        }
+    }
 
-        return None;
+    fn read_integer(&mut self) {
+        while let Some('0'..='9') = self.ch {
+            self.read_char();
+        }
     }
 
-    fn read_identifier(&mut self) -> Option<String> {
-        if let Some(start_position) = self.position {
-            while let Some('a'..='z' | 'A'..='Z' | '_') = self.ch {
-                self.read_char();
+    fn read_identifier(&mut self) -> &'src str {
+        let start_position = self.position;
+
+        while let Some('a'..='z' | 'A'..='Z' | '_') = self.ch {
+            self.read_char();
+        }
+
+        return &self.input[start_position..self.position];
+    }
+
+    /// Consumes a string literal up to, but not including, its closing quote.
+    /// Escapes are only skipped here so `\"` does not end the string, the
+    /// parser decodes them. Returns false if the input ends first.
+    fn read_string(&mut self) -> bool {
+        self.read_char();
+
+        loop {
+            match self.ch {
+                Some('"') => return true,
+                Some('\\') => {
+                    self.read_char();
+                    if self.ch.is_none() {
+                        return false;
+                    }
+                }
+                None => return false,
+                Some(_) => {}
             }
 
-            return Some(
-                self.input[start_position as usize..self.position.unwrap() as usize].to_string(),
-            );
+            self.read_char();
+        }
+    }
+
+    pub fn next_token(&mut self) -> Token<'src> {
+        self.skip_whitespace_and_comments();
+
+        let start = self.position;
+        let (line, column) = (self.line, self.column);
+        let kind = self.read_token_kind();
+        let span = Span::new(start, self.position, line, column);
+        let literal = &self.input[start..self.position];
+
+        if kind == TokenKind::ILLEGAL {
+            let error = match literal.chars().next() {
+                Some('"') => LexError::UnterminatedString { span },
+                found => LexError::IllegalCharacter {
+                    found: found.unwrap_or(char::REPLACEMENT_CHARACTER),
+                    span,
+                },
+            };
+            self.errors.push(error);
+        }
+
+        return Token {
+            kind,
+            span,
+            literal,
         };
+    }
 
-        return None;
+    /// Hands over the errors recorded since the last call.
+    pub fn take_errors(&mut self) -> Vec<LexError> {
+        return std::mem::take(&mut self.errors);
     }
 
-    pub fn next_token(&mut self) -> Token {
-        let token: Token;
+    fn read_token_kind(&mut self) -> TokenKind {
+        let token: TokenKind;
 
-        self.skip_whitespace();
+        if self.at_doc_comment() {
+            self.skip_line();
+            return TokenKind::DOCCOMMENT;
+        }
 
         if let Some(char_literal) = self.ch {
-            if let Some(t) = Token::from_char(char_literal) {
+            if let Some(t) = TokenKind::from_char(char_literal) {
                 token = t
             } else {
                 match char_literal {
-                    '=' => {
-                        if let Some('=') = self.peek_char() {
-                            self.read_char();
-                            token = Token::EQ
-                        } else {
-                            token = Token::ASSIGN
-                        }
+                    '=' => token = self.read_with_eq(TokenKind::ASSIGN, TokenKind::EQ),
+                    '!' => token = self.read_with_eq(TokenKind::BANG, TokenKind::NOTEQ),
+                    '+' => token = self.read_with_eq(TokenKind::PLUS, TokenKind::PLUSASSIGN),
+                    '-' => token = self.read_with_eq(TokenKind::MINUS, TokenKind::MINUSASSIGN),
+                    '*' if self.peek_char() == Some('*') => {
+                        self.read_char();
+                        token = TokenKind::POWER
+                    }
+                    '*' => {
+                        token = self.read_with_eq(TokenKind::ASTERISK, TokenKind::ASTERISKASSIGN)
                     }
-                    '!' => {
-                        if let Some('=') = self.peek_char() {
-                            self.read_char();
-                            token = Token::NOTEQ
+                    '<' => token = self.read_with_eq(TokenKind::LT, TokenKind::LTEQ),
+                    '>' => token = self.read_with_eq(TokenKind::GT, TokenKind::GTEQ),
+                    '/' => token = self.read_with_eq(TokenKind::SLASH, TokenKind::SLASHASSIGN),
+                    '&' if self.peek_char() == Some('&') => {
+                        self.read_char();
+                        token = TokenKind::AND
+                    }
+                    '|' if self.peek_char() == Some('|') => {
+                        self.read_char();
+                        token = TokenKind::OR
+                    }
+                    'a'..='z' | 'A'..='Z' | '_' => {
+                        return TokenKind::from_identifier(self.read_identifier())
+                    }
+                    '0'..='9' => {
+                        self.read_integer();
+                        return TokenKind::INT;
+                    }
+                    '"' => {
+                        if self.read_string() {
+                            token = TokenKind::STRING
                         } else {
-                            token = Token::BANG
+                            token = TokenKind::ILLEGAL
                         }
                     }
-                    keyword_char => match keyword_char {
-                        'a'..='z' | 'A'..='Z' | '_' => match self.read_identifier() {
-                            None => {
-                                return {
-                                    println!("Unrecognized keyword char: {:?}", keyword_char);
-                                    Token::ILLEGAL
-                                };
-                            }
-                            Some(token_literal) => return Token::from_identifier(token_literal),
-                        },
-                        '0'..='9' => match self.read_integer() {
-                            None => {
-                                return {
-                                    println!("Unrecognized number char: {:?}", keyword_char);
-                                    Token::ILLEGAL
-                                };
-                            }
-                            Some(interger) => return Token::from_interger_string(interger),
-                        },
-                        _ => {
-                            return {
-                                println!("Unrecognized char: {:?}", char_literal);
-                                Token::ILLEGAL
-                            };
-                        }
-                    },
+                    _ => token = TokenKind::ILLEGAL,
                 }
             }
         } else {
-            token = Token::EOF
+            token = TokenKind::EOF
         }
 
         self.read_char();
         return token;
     }
+
+    /// `with_eq` when the current character is followed by `=`, which is
+    /// then consumed, `alone` otherwise.
+    fn read_with_eq(&mut self, alone: TokenKind, with_eq: TokenKind) -> TokenKind {
+        if let Some('=') = self.peek_char() {
+            self.read_char();
+            return with_eq;
+        }
+
+        return alone;
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{lexer::Lexer, token::Token};
+    use crate::{
+        diagnostic::Diagnostic,
+        lexer::{LexError, Lexer},
+        span::Span,
+        token::TokenKind,
+    };
 
     #[test]
     fn test_new_lexer() {
-        let test_lexer = Lexer::new("{}".to_string());
+        let test_lexer = Lexer::new("{}");
 
         assert_eq!(test_lexer.input, "{}");
-        assert_eq!(test_lexer.position, Some(0));
-        assert_eq!(test_lexer.read_position, Some(1));
+        assert_eq!(test_lexer.position, 0);
+        assert_eq!(test_lexer.read_position, 1);
         assert_eq!(test_lexer.ch, Some('{'))
     }
 
+    #[test]
+    fn test_lexer_literal_at_end_of_input() {
+        let mut test_lexer = Lexer::new("foobar 838383");
+
+        let identifier = test_lexer.next_token();
+        assert_eq!(
+            (identifier.kind, identifier.literal),
+            (TokenKind::IDENT, "foobar")
+        );
+
+        let interger = test_lexer.next_token();
+        assert_eq!(
+            (interger.kind, interger.literal),
+            (TokenKind::INT, "838383")
+        );
+
+        assert_eq!(test_lexer.next_token().kind, TokenKind::EOF);
+    }
+
     #[test]
     fn test_lexer_next_token() {
         let input = "let five = 5;
@@ -175,7 +352,7 @@ mod tests {
         };
         
         let result = add(five, ten);
-        !-/*5;
+        !-/ *5;
         5 < 10 > 5;
     
         if (5 < 10) {
@@ -185,94 +362,409 @@ mod tests {
         }
         
         10 == 10;
-        10 != 9;"
+        10 != 9;
+        [1, 2];
+        {\"foo\": \"bar\"}
+        while for in break continue
+        x += 1; x -= 1; x *= 2; x /= 2;
+        a && b || c
+        1 <= 2 >= 3 % 4 ** 5"
             .to_string();
 
-        let expected_types: Vec<Token> = vec![
-            Token::LET,
-            Token::IDENT(String::from("five")),
-            Token::ASSIGN,
-            Token::INT(String::from("5")),
-            Token::SEMICOLON,
-            Token::LET,
-            Token::IDENT(String::from("ten")),
-            Token::ASSIGN,
-            Token::INT(String::from("10")),
-            Token::SEMICOLON,
-            Token::LET,
-            Token::IDENT(String::from("add")),
-            Token::ASSIGN,
-            Token::FUNCTION,
-            Token::LPAREN,
-            Token::IDENT(String::from("x")),
-            Token::COMMA,
-            Token::IDENT(String::from("y")),
-            Token::RPAREN,
-            Token::LBRACE,
-            Token::IDENT(String::from("x")),
-            Token::PLUS,
-            Token::IDENT(String::from("y")),
-            Token::SEMICOLON,
-            Token::RBRACE,
-            Token::SEMICOLON,
-            Token::LET,
-            Token::IDENT(String::from("result")),
-            Token::ASSIGN,
-            Token::IDENT(String::from("add")),
-            Token::LPAREN,
-            Token::IDENT(String::from("five")),
-            Token::COMMA,
-            Token::IDENT(String::from("ten")),
-            Token::RPAREN,
-            Token::SEMICOLON,
-            Token::BANG,
-            Token::MINUS,
-            Token::SLASH,
-            Token::ASTERISK,
-            Token::INT(String::from("5")),
-            Token::SEMICOLON,
-            Token::INT(String::from("5")),
-            Token::LT,
-            Token::INT(String::from("10")),
-            Token::GT,
-            Token::INT(String::from("5")),
-            Token::SEMICOLON,
-            Token::IF,
-            Token::LPAREN,
-            Token::INT(String::from("5")),
-            Token::LT,
-            Token::INT(String::from("10")),
-            Token::RPAREN,
-            Token::LBRACE,
-            Token::RETURN,
-            Token::TRUE,
-            Token::SEMICOLON,
-            Token::RBRACE,
-            Token::ELSE,
-            Token::LBRACE,
-            Token::RETURN,
-            Token::FALSE,
-            Token::SEMICOLON,
-            Token::RBRACE,
-            Token::INT(String::from("10")),
-            Token::EQ,
-            Token::INT(String::from("10")),
-            Token::SEMICOLON,
-            Token::INT(String::from("10")),
-            Token::NOTEQ,
-            Token::INT(String::from("9")),
-            Token::SEMICOLON,
-            Token::EOF,
+        let expected_types: Vec<(TokenKind, &str)> = vec![
+            (TokenKind::LET, "let"),
+            (TokenKind::IDENT, "five"),
+            (TokenKind::ASSIGN, "="),
+            (TokenKind::INT, "5"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::LET, "let"),
+            (TokenKind::IDENT, "ten"),
+            (TokenKind::ASSIGN, "="),
+            (TokenKind::INT, "10"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::LET, "let"),
+            (TokenKind::IDENT, "add"),
+            (TokenKind::ASSIGN, "="),
+            (TokenKind::FUNCTION, "fn"),
+            (TokenKind::LPAREN, "("),
+            (TokenKind::IDENT, "x"),
+            (TokenKind::COMMA, ","),
+            (TokenKind::IDENT, "y"),
+            (TokenKind::RPAREN, ")"),
+            (TokenKind::LBRACE, "{"),
+            (TokenKind::IDENT, "x"),
+            (TokenKind::PLUS, "+"),
+            (TokenKind::IDENT, "y"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::RBRACE, "}"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::LET, "let"),
+            (TokenKind::IDENT, "result"),
+            (TokenKind::ASSIGN, "="),
+            (TokenKind::IDENT, "add"),
+            (TokenKind::LPAREN, "("),
+            (TokenKind::IDENT, "five"),
+            (TokenKind::COMMA, ","),
+            (TokenKind::IDENT, "ten"),
+            (TokenKind::RPAREN, ")"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::BANG, "!"),
+            (TokenKind::MINUS, "-"),
+            (TokenKind::SLASH, "/"),
+            (TokenKind::ASTERISK, "*"),
+            (TokenKind::INT, "5"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::INT, "5"),
+            (TokenKind::LT, "<"),
+            (TokenKind::INT, "10"),
+            (TokenKind::GT, ">"),
+            (TokenKind::INT, "5"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::IF, "if"),
+            (TokenKind::LPAREN, "("),
+            (TokenKind::INT, "5"),
+            (TokenKind::LT, "<"),
+            (TokenKind::INT, "10"),
+            (TokenKind::RPAREN, ")"),
+            (TokenKind::LBRACE, "{"),
+            (TokenKind::RETURN, "return"),
+            (TokenKind::TRUE, "true"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::RBRACE, "}"),
+            (TokenKind::ELSE, "else"),
+            (TokenKind::LBRACE, "{"),
+            (TokenKind::RETURN, "return"),
+            (TokenKind::FALSE, "false"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::RBRACE, "}"),
+            (TokenKind::INT, "10"),
+            (TokenKind::EQ, "=="),
+            (TokenKind::INT, "10"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::INT, "10"),
+            (TokenKind::NOTEQ, "!="),
+            (TokenKind::INT, "9"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::LBRACKET, "["),
+            (TokenKind::INT, "1"),
+            (TokenKind::COMMA, ","),
+            (TokenKind::INT, "2"),
+            (TokenKind::RBRACKET, "]"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::LBRACE, "{"),
+            (TokenKind::STRING, "\"foo\""),
+            (TokenKind::COLON, ":"),
+            (TokenKind::STRING, "\"bar\""),
+            (TokenKind::RBRACE, "}"),
+            (TokenKind::WHILE, "while"),
+            (TokenKind::FOR, "for"),
+            (TokenKind::IN, "in"),
+            (TokenKind::BREAK, "break"),
+            (TokenKind::CONTINUE, "continue"),
+            (TokenKind::IDENT, "x"),
+            (TokenKind::PLUSASSIGN, "+="),
+            (TokenKind::INT, "1"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::IDENT, "x"),
+            (TokenKind::MINUSASSIGN, "-="),
+            (TokenKind::INT, "1"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::IDENT, "x"),
+            (TokenKind::ASTERISKASSIGN, "*="),
+            (TokenKind::INT, "2"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::IDENT, "x"),
+            (TokenKind::SLASHASSIGN, "/="),
+            (TokenKind::INT, "2"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::IDENT, "a"),
+            (TokenKind::AND, "&&"),
+            (TokenKind::IDENT, "b"),
+            (TokenKind::OR, "||"),
+            (TokenKind::IDENT, "c"),
+            (TokenKind::INT, "1"),
+            (TokenKind::LTEQ, "<="),
+            (TokenKind::INT, "2"),
+            (TokenKind::GTEQ, ">="),
+            (TokenKind::INT, "3"),
+            (TokenKind::PERCENT, "%"),
+            (TokenKind::INT, "4"),
+            (TokenKind::POWER, "**"),
+            (TokenKind::INT, "5"),
+            (TokenKind::EOF, ""),
         ];
 
-        let mut test_lexer = Lexer::new(input.clone());
+        let mut test_lexer = Lexer::new(&input);
 
         for i in 0..expected_types.len() {
             let t = test_lexer.next_token();
 
             println!("Token num: {}. Token: {:?}", i, t);
 
-            assert_eq!(t, expected_types[i]);
+            assert_eq!((t.kind, t.literal), expected_types[i]);
+        }
+    }
+
+    #[test]
+    fn test_lexer_token_spans() {
+        let input = "let x = 10;
+  x == 5;"
+            .to_string();
+
+        let expected_spans = vec![
+            (TokenKind::LET, Span::new(0, 3, 1, 1)),
+            (TokenKind::IDENT, Span::new(4, 5, 1, 5)),
+            (TokenKind::ASSIGN, Span::new(6, 7, 1, 7)),
+            (TokenKind::INT, Span::new(8, 10, 1, 9)),
+            (TokenKind::SEMICOLON, Span::new(10, 11, 1, 11)),
+            (TokenKind::IDENT, Span::new(14, 15, 2, 3)),
+            (TokenKind::EQ, Span::new(16, 18, 2, 5)),
+            (TokenKind::INT, Span::new(19, 20, 2, 8)),
+            (TokenKind::SEMICOLON, Span::new(20, 21, 2, 9)),
+            (TokenKind::EOF, Span::new(21, 21, 2, 10)),
+        ];
+
+        let mut test_lexer = Lexer::new(&input);
+
+        for (kind, span) in expected_spans {
+            let t = test_lexer.next_token();
+
+            assert_eq!(t.kind, kind);
+            assert_eq!(t.span, span, "span of {:?}", kind);
         }
     }
+
+    #[test]
+    fn test_lexer_single_ampersand_and_pipe() {
+        let mut test_lexer = Lexer::new("a & b | c");
+
+        while test_lexer.next_token().kind != TokenKind::EOF {}
+
+        let hints: Vec<_> = test_lexer
+            .take_errors()
+            .iter()
+            .map(|error| (error.to_string(), error.hint()))
+            .collect();
+
+        assert_eq!(
+            hints,
+            vec![
+                (
+                    String::from("illegal character `&`"),
+                    Some(String::from("did you mean `&&`?"))
+                ),
+                (
+                    String::from("illegal character `|`"),
+                    Some(String::from("did you mean `||`?"))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_skips_illegal_char() {
+        let mut test_lexer = Lexer::new("@5");
+
+        let illegal = test_lexer.next_token();
+        assert_eq!(illegal.kind, TokenKind::ILLEGAL);
+        assert_eq!(illegal.span, Span::new(0, 1, 1, 1));
+        assert_eq!(test_lexer.next_token().kind, TokenKind::INT);
+        assert_eq!(
+            test_lexer.take_errors(),
+            vec![LexError::IllegalCharacter {
+                found: '@',
+                span: Span::new(0, 1, 1, 1)
+            }]
+        );
+        assert!(test_lexer.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_lexer_non_ascii_input() {
+        let input = "let é = \"ü\";\nx ≠ 1;".to_string();
+
+        let expected_tokens = vec![
+            (TokenKind::LET, Span::new(0, 3, 1, 1)),
+            (TokenKind::ILLEGAL, Span::new(4, 6, 1, 5)),
+            (TokenKind::ASSIGN, Span::new(7, 8, 1, 7)),
+            (TokenKind::STRING, Span::new(9, 13, 1, 9)),
+            (TokenKind::SEMICOLON, Span::new(13, 14, 1, 12)),
+            (TokenKind::IDENT, Span::new(15, 16, 2, 1)),
+            (TokenKind::ILLEGAL, Span::new(17, 20, 2, 3)),
+            (TokenKind::INT, Span::new(21, 22, 2, 5)),
+            (TokenKind::SEMICOLON, Span::new(22, 23, 2, 6)),
+            (TokenKind::EOF, Span::new(23, 23, 2, 7)),
+        ];
+
+        let mut test_lexer = Lexer::new(&input);
+
+        for (kind, span) in expected_tokens {
+            let t = test_lexer.next_token();
+
+            assert_eq!(t.kind, kind);
+            assert_eq!(t.span, span, "span of {:?}", kind);
+        }
+
+        let illegal_chars: Vec<char> = test_lexer
+            .take_errors()
+            .into_iter()
+            .map(|error| match error {
+                LexError::IllegalCharacter { found, .. } => found,
+                error => panic!("Unexpected error {:?}", error),
+            })
+            .collect();
+
+        assert_eq!(illegal_chars, vec!['é', '≠']);
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture bench_` to see throughput.
+    #[test]
+    #[ignore]
+    fn bench_lexer_throughput() {
+        let chunk = "let result = add(five, ten) * (10 - 2) / 4;
+        if (result != 10) { return !true; } else { return result == 9; }
+        ";
+        let input = chunk.repeat(4 * 1024 * 1024 / chunk.len());
+        let bytes = input.len();
+
+        let start = std::time::Instant::now();
+        let mut test_lexer = Lexer::new(&input);
+        let mut tokens = 0;
+
+        while test_lexer.next_token().kind != TokenKind::EOF {
+            tokens += 1;
+        }
+
+        let elapsed = start.elapsed();
+        println!(
+            "lexed {} bytes into {} tokens in {:?} ({:.1} MB/s)",
+            bytes,
+            tokens,
+            elapsed,
+            bytes as f64 / 1_000_000.0 / elapsed.as_secs_f64()
+        );
+    }
+
+    #[test]
+    fn test_lexer_string_literals() {
+        let input = r#""foobar" "foo bar" "say \"hi\"" "a\\" "multi
+line";"#;
+
+        let expected_tokens = vec![
+            (TokenKind::STRING, r#""foobar""#),
+            (TokenKind::STRING, r#""foo bar""#),
+            (TokenKind::STRING, r#""say \"hi\"""#),
+            (TokenKind::STRING, r#""a\\""#),
+            (TokenKind::STRING, "\"multi\nline\""),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::EOF, ""),
+        ];
+
+        let mut test_lexer = Lexer::new(input);
+
+        for expected in expected_tokens {
+            let t = test_lexer.next_token();
+
+            assert_eq!((t.kind, t.literal), expected);
+        }
+
+        assert!(test_lexer.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_lexer_unterminated_string() {
+        let inputs = vec![r#"let a = "abc"#, r#"let a = "abc\""#, r#"let a = "abc\"#];
+
+        for input in inputs {
+            let mut test_lexer = Lexer::new(input);
+
+            test_lexer.next_token();
+            test_lexer.next_token();
+            test_lexer.next_token();
+
+            let t = test_lexer.next_token();
+            let span = Span::new(8, input.len(), 1, 9);
+
+            assert_eq!(t.kind, TokenKind::ILLEGAL, "{}", input);
+            assert_eq!(t.span, span, "{}", input);
+            assert_eq!(test_lexer.next_token().kind, TokenKind::EOF);
+            assert_eq!(
+                test_lexer.take_errors(),
+                vec![LexError::UnterminatedString { span }]
+            );
+        }
+    }
+
+    #[test]
+    fn test_lexer_skips_comments() {
+        let input = "// leading comment
+let a = 1; // trailing comment
+/* block */ let b /* inline */ = 2;
+/* outer /* nested */ still comment */
+//// not a doc comment
+a / b;";
+
+        let expected_tokens = vec![
+            (TokenKind::LET, "let"),
+            (TokenKind::IDENT, "a"),
+            (TokenKind::ASSIGN, "="),
+            (TokenKind::INT, "1"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::LET, "let"),
+            (TokenKind::IDENT, "b"),
+            (TokenKind::ASSIGN, "="),
+            (TokenKind::INT, "2"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::IDENT, "a"),
+            (TokenKind::SLASH, "/"),
+            (TokenKind::IDENT, "b"),
+            (TokenKind::SEMICOLON, ";"),
+            (TokenKind::EOF, ""),
+        ];
+
+        let mut test_lexer = Lexer::new(input);
+
+        for expected in expected_tokens {
+            let t = test_lexer.next_token();
+
+            assert_eq!((t.kind, t.literal), expected);
+        }
+
+        assert!(test_lexer.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_lexer_doc_comments() {
+        let input = "/// Adds two numbers.\n///\nlet add = 1;";
+
+        let expected_tokens = vec![
+            (TokenKind::DOCCOMMENT, "/// Adds two numbers."),
+            (TokenKind::DOCCOMMENT, "///"),
+            (TokenKind::LET, "let"),
+        ];
+
+        let mut test_lexer = Lexer::new(input);
+
+        for expected in expected_tokens {
+            let t = test_lexer.next_token();
+
+            assert_eq!((t.kind, t.literal), expected);
+        }
+    }
+
+    #[test]
+    fn test_lexer_unterminated_block_comment() {
+        let input = "1; /* outer /* inner */";
+        let mut test_lexer = Lexer::new(input);
+
+        assert_eq!(test_lexer.next_token().kind, TokenKind::INT);
+        assert_eq!(test_lexer.next_token().kind, TokenKind::SEMICOLON);
+        assert_eq!(test_lexer.next_token().kind, TokenKind::EOF);
+        assert_eq!(
+            test_lexer.take_errors(),
+            vec![LexError::UnterminatedComment {
+                span: Span::new(3, 23, 1, 4)
+            }]
+        );
+    }
 }
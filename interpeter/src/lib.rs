@@ -0,0 +1,34 @@
+//! An interpreter for the Monkey language.
+//!
+//! [`Interpreter`] is the easiest way in: it parses and evaluates source
+//! text while keeping bindings between calls. [`Lexer`] and [`Parser`] are
+//! exposed for tools that only need tokens or the AST, and
+//! [`compiler`] and [`vm`] run programs as bytecode instead of walking
+//! the tree.
+
+pub mod ast;
+mod builtins;
+pub mod code;
+pub mod compiler;
+pub mod convert;
+#[cfg(test)]
+mod corpus;
+pub mod diagnostic;
+pub mod disassembler;
+mod evaluator;
+mod interpreter;
+pub mod lexer;
+pub mod object;
+pub mod parser;
+pub mod repl;
+pub mod span;
+mod symbol_table;
+pub mod token;
+pub mod vm;
+
+pub use ast::Program;
+pub use evaluator::RuntimeError;
+pub use interpreter::{Error, Interpreter};
+pub use lexer::Lexer;
+pub use object::{Object, Value};
+pub use parser::Parser;
@@ -1,9 +1,88 @@
-mod lexer;
-mod token;
-mod repl;
-mod parser;
-mod ast;
+use std::{env, fs, process, thread};
+
+use interpeter::{disassembler::disassemble_source, repl, Interpreter};
+
+const USAGE: &str = "usage: interpeter [<file> | --disasm <file>]";
+
+/// The evaluator recurses on the native stack, this leaves room for the
+/// deepest calls it allows even in a debug build.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
 
 fn main() {
-    repl::start(std::io::stdin());
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match args.as_slice() {
+        [] => {
+            let repl = on_large_stack(|| repl::start(std::io::stdin().lock(), std::io::stdout()));
+
+            if let Err(error) = repl {
+                eprintln!("error: {}", error);
+                process::exit(1);
+            }
+        }
+        [flag, path] if flag == "--disasm" => disasm(path),
+        [path] if !path.starts_with('-') => {
+            let source = read_source(path);
+
+            if !on_large_stack(move || run(&source)) {
+                process::exit(1);
+            }
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+/// Runs `f` on a thread with `STACK_SIZE` bytes of stack and waits for it.
+fn on_large_stack<T, F>(f: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let thread = thread::Builder::new().stack_size(STACK_SIZE).spawn(f);
+
+    match thread.map(|thread| thread.join()) {
+        Ok(Ok(value)) => return value,
+        Ok(Err(panic)) => std::panic::resume_unwind(panic),
+        Err(error) => {
+            eprintln!("error: cannot start the interpreter: {}", error);
+            process::exit(1);
+        }
+    }
+}
+
+/// Evaluates a whole script, reporting whether it ran without errors.
+fn run(source: &str) -> bool {
+    match Interpreter::new().eval(source) {
+        Ok(_) => return true,
+        Err(error) => {
+            eprint!("{}", error.render(source));
+            return false;
+        }
+    }
+}
+
+/// Prints the bytecode the compiler produces for the file at `path`.
+fn disasm(path: &str) {
+    let source = read_source(path);
+
+    match disassemble_source(&source) {
+        Ok(listing) => print!("{}", listing),
+        Err(error) => {
+            eprint!("{}", error.render(&source));
+            process::exit(1);
+        }
+    }
+}
+
+fn read_source(path: &str) -> String {
+    match fs::read_to_string(path) {
+        Ok(source) => return source,
+        Err(error) => {
+            eprintln!("error: cannot read {}: {}", path, error);
+            process::exit(1);
+        }
+    }
 }
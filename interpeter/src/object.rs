@@ -0,0 +1,297 @@
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+use crate::{ast::BlockStatement, interpreter::Error};
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Object {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+    Array(Rc<Vec<Object>>),
+    Hash(Rc<HashMap<HashKey, Object>>),
+    Function(Rc<Function>),
+    CompiledFunction(Rc<CompiledFunction>),
+    Closure(Rc<Closure>),
+    Host(Rc<HostFunction>),
+    Null,
+}
+
+/// The name embedders see for [`Object`].
+pub type Value = Object;
+
+impl Object {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Integer(_) => return "INTEGER",
+            Object::Boolean(_) => return "BOOLEAN",
+            Object::String(_) => return "STRING",
+            Object::Array(_) => return "ARRAY",
+            Object::Hash(_) => return "HASH",
+            Object::Function(_) | Object::CompiledFunction(_) | Object::Closure(_) => {
+                return "FUNCTION"
+            }
+            Object::Host(_) => return "BUILTIN",
+            Object::Null => return "NULL",
+        }
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Object::Null | Object::Boolean(false) => return false,
+            _ => return true,
+        }
+    }
+
+    /// The key this value is stored under in a hash, `None` for values
+    /// that cannot be used as keys.
+    pub fn hash_key(&self) -> Option<HashKey> {
+        match self {
+            Object::Integer(value) => return Some(HashKey::Integer(*value)),
+            Object::Boolean(value) => return Some(HashKey::Boolean(*value)),
+            Object::String(value) => return Some(HashKey::String(value.to_owned())),
+            _ => return None,
+        }
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Object::Integer(value) => write!(f, "{}", value),
+            Object::Boolean(value) => write!(f, "{}", value),
+            Object::String(value) => write!(f, "{}", value),
+            Object::Array(elements) => {
+                write!(f, "[")?;
+
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write_nested(f, element)?;
+                }
+
+                write!(f, "]")
+            }
+            Object::Hash(pairs) => {
+                // Sorted so printing the same hash always gives the same text.
+                let mut pairs: Vec<_> = pairs.iter().collect();
+                pairs.sort_by(|a, b| a.0.cmp(b.0));
+
+                write!(f, "{{")?;
+
+                for (i, (key, value)) in pairs.into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}: ", key)?;
+                    write_nested(f, value)?;
+                }
+
+                write!(f, "}}")
+            }
+            Object::Function(function) => write!(f, "{}", function),
+            Object::CompiledFunction(function) => write!(f, "{}", function),
+            Object::Closure(closure) => write!(f, "{}", closure.function),
+            Object::Host(function) => write!(f, "builtin {}", function.name),
+            Object::Null => write!(f, "null"),
+        }
+    }
+}
+
+/// Strings inside arrays and hashes are quoted so `["a, b"]` and
+/// `["a", "b"]` print differently.
+fn write_nested(f: &mut fmt::Formatter<'_>, value: &Object) -> fmt::Result {
+    match value {
+        Object::String(value) => write!(f, "{:?}", value),
+        _ => write!(f, "{}", value),
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+}
+
+impl From<HashKey> for Object {
+    fn from(value: HashKey) -> Self {
+        match value {
+            HashKey::Integer(value) => return Object::Integer(value),
+            HashKey::Boolean(value) => return Object::Boolean(value),
+            HashKey::String(value) => return Object::String(value),
+        }
+    }
+}
+
+impl fmt::Display for HashKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashKey::Integer(value) => write!(f, "{}", value),
+            HashKey::Boolean(value) => write!(f, "{}", value),
+            HashKey::String(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+/// A function value. It keeps the environment it was defined in alive so
+/// its body can still see those bindings when called from elsewhere.
+pub struct Function {
+    pub params: Vec<String>,
+    pub body: Rc<BlockStatement>,
+    pub env: Environment,
+}
+
+/// Functions are only equal to themselves.
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        return std::ptr::eq(self, other);
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Function({})", self)
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fn({}) {}", self.params.join(", "), self.body)
+    }
+}
+
+/// A function body lowered to bytecode by the compiler.
+#[derive(PartialEq, Debug)]
+pub struct CompiledFunction {
+    pub instructions: Vec<u8>,
+    /// Source line of each instruction as `(offset, line)` pairs.
+    pub lines: Vec<(usize, usize)>,
+    /// Slots to reserve on the stack, parameters included.
+    pub num_locals: usize,
+    pub num_params: usize,
+    /// Where each upvalue of a closure over this function comes from.
+    pub captures: Vec<Capture>,
+}
+
+/// Where the VM finds a captured variable when it creates a closure.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Capture {
+    /// `true` for a local of the function creating the closure, `false`
+    /// for one of that function's own upvalues.
+    pub is_local: bool,
+    pub index: usize,
+}
+
+/// A compiled function together with the variables it captured.
+#[derive(PartialEq, Debug)]
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+/// A captured variable. It stays `Open` and reads the stack slot of the
+/// frame that owns it until that frame returns, then moves the value in.
+/// Closures sharing an `Upvalue` see each other's writes.
+#[derive(PartialEq, Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Object),
+}
+
+impl fmt::Display for CompiledFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "compiled fn/{}", self.num_params)
+    }
+}
+
+pub type HostFn = dyn Fn(&[Value]) -> Result<Value, Error>;
+
+/// A function implemented in Rust and handed to scripts by the embedder.
+pub struct HostFunction {
+    pub name: String,
+    pub func: Box<HostFn>,
+}
+
+impl PartialEq for HostFunction {
+    fn eq(&self, other: &Self) -> bool {
+        return std::ptr::eq(self, other);
+    }
+}
+
+impl fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HostFunction({})", self.name)
+    }
+}
+
+#[derive(Default)]
+struct Scope {
+    store: HashMap<String, Object>,
+    outer: Option<Environment>,
+}
+
+/// Handle to a scope of bindings. Clones share the same scope, which is how
+/// functions capture the environment they were defined in.
+#[derive(Clone, Default)]
+pub struct Environment {
+    scope: Rc<RefCell<Scope>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        return Environment::default();
+    }
+
+    /// New empty scope whose lookups fall back to `outer`.
+    pub fn new_enclosed(outer: &Environment) -> Self {
+        let scope = Scope {
+            store: HashMap::new(),
+            outer: Some(outer.clone()),
+        };
+
+        return Environment {
+            scope: Rc::new(RefCell::new(scope)),
+        };
+    }
+
+    pub fn get(&self, name: &str) -> Option<Object> {
+        let scope = self.scope.borrow();
+
+        match scope.store.get(name) {
+            Some(value) => return Some(value.clone()),
+            None => return scope.outer.as_ref().and_then(|outer| outer.get(name)),
+        }
+    }
+
+    pub fn set(&mut self, name: String, value: Object) {
+        self.scope.borrow_mut().store.insert(name, value);
+    }
+
+    /// Updates `name` in the innermost scope that binds it, returns false
+    /// when no scope does.
+    pub fn assign(&mut self, name: &str, value: Object) -> bool {
+        return self.update(name, |slot| *slot = value).is_some();
+    }
+
+    /// Calls `f` on the value of `name` in the innermost scope that binds
+    /// it, `None` when no scope does. The value is not cloned, so `f` can
+    /// change a container it holds without copying it.
+    pub fn update<R>(&mut self, name: &str, f: impl FnOnce(&mut Object) -> R) -> Option<R> {
+        let mut scope = self.scope.borrow_mut();
+
+        if let Some(slot) = scope.store.get_mut(name) {
+            return Some(f(slot));
+        }
+
+        match scope.outer.clone() {
+            Some(mut outer) => {
+                drop(scope);
+                return outer.update(name, f);
+            }
+            None => return None,
+        }
+    }
+}
@@ -1,448 +1,2179 @@
+use std::{fmt, iter::Peekable, rc::Rc, str::CharIndices};
+
 use crate::{
-    ast::{Expresion, Operator, Precedence, Program, Statement},
-    lexer::Lexer,
-    token::Token,
+    ast::{BlockStatement, Expresion, Operator, Precedence, Program, Statement},
+    diagnostic::Diagnostic,
+    lexer::{LexError, Lexer},
+    span::Span,
+    token::{Token, TokenKind},
 };
 
-pub struct Parser {
-    lexer: Lexer,
-    cur_token: Token,
-    next_token: Token,
+pub struct Parser<'src> {
+    lexer: Lexer<'src>,
+    cur_token: Token<'src>,
+    next_token: Token<'src>,
+    cur_doc: Option<String>,
+    next_doc: Option<String>,
+    errors: Vec<ParseError>,
+    /// Loops enclosing the current token within the innermost function,
+    /// `break` and `continue` are only allowed when this is not zero.
+    loop_depth: usize,
+    /// Expressions enclosing the current token, see `MAX_NESTING`.
+    nesting: usize,
+}
+
+/// How deeply expressions may nest, counting one level for every
+/// expression inside another and for every operator applied to what came
+/// before it. The evaluator and compiler recurse over the tree, so without
+/// a limit deeply nested input would overflow the native stack.
+pub const MAX_NESTING: usize = 256;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum ParseError {
+    UnexpectedToken {
+        expected: TokenKind,
+        found: TokenKind,
+        span: Span,
+    },
+    ExpectedExpression {
+        found: TokenKind,
+        span: Span,
+    },
+    MissingIdentifier {
+        found: TokenKind,
+        span: Span,
+    },
+    MissingAssign {
+        found: TokenKind,
+        span: Span,
+    },
+    InvalidInteger {
+        literal: String,
+        span: Span,
+    },
+    InvalidEscape {
+        sequence: String,
+        span: Span,
+    },
+    OutsideLoop {
+        keyword: TokenKind,
+        span: Span,
+    },
+    InvalidAssignmentTarget {
+        span: Span,
+    },
+    TooDeeplyNested {
+        span: Span,
+    },
+    Lex(LexError),
 }
 
-type ParseError = String;
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedToken {
+                expected, found, ..
+            } => write!(
+                f,
+                "expected {}, found {}",
+                describe(expected),
+                describe(found)
+            ),
+            ParseError::ExpectedExpression { found, .. } => {
+                write!(f, "expected an expression, found {}", describe(found))
+            }
+            ParseError::MissingIdentifier { found, .. } => {
+                write!(
+                    f,
+                    "expected an identifier after `let`, found {}",
+                    describe(found)
+                )
+            }
+            ParseError::MissingAssign { found, .. } => {
+                write!(
+                    f,
+                    "expected `=` after the let binding name, found {}",
+                    describe(found)
+                )
+            }
+            ParseError::InvalidInteger { literal, .. } => {
+                write!(f, "integer literal `{}` is out of range", literal)
+            }
+            ParseError::InvalidEscape { sequence, .. } => {
+                write!(f, "invalid escape sequence `{}`", sequence)
+            }
+            ParseError::OutsideLoop { keyword, .. } => {
+                write!(f, "`{}` outside of a loop", keyword)
+            }
+            ParseError::InvalidAssignmentTarget { .. } => write!(f, "invalid assignment target"),
+            ParseError::TooDeeplyNested { .. } => write!(f, "expression is nested too deeply"),
+            ParseError::Lex(error) => write!(f, "{}", error),
+        }
+    }
+}
 
-impl Parser {
-    pub fn new(input: String) -> Parser {
-        let mut lexer = Lexer::new(input);
-        let cur_token = lexer.next_token();
-        let next_token = lexer.next_token();
+impl std::error::Error for ParseError {}
+
+impl Diagnostic for ParseError {
+    fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::ExpectedExpression { span, .. }
+            | ParseError::MissingIdentifier { span, .. }
+            | ParseError::MissingAssign { span, .. }
+            | ParseError::InvalidInteger { span, .. }
+            | ParseError::InvalidEscape { span, .. }
+            | ParseError::OutsideLoop { span, .. }
+            | ParseError::InvalidAssignmentTarget { span }
+            | ParseError::TooDeeplyNested { span } => return *span,
+            ParseError::Lex(error) => return error.span(),
+        }
+    }
 
-        Parser {
-            lexer,
-            cur_token,
-            next_token,
+    fn hint(&self) -> Option<String> {
+        match self {
+            ParseError::MissingIdentifier { .. } | ParseError::MissingAssign { .. } => {
+                return Some(String::from(
+                    "let bindings take the form `let <name> = <value>;`",
+                ))
+            }
+            ParseError::InvalidInteger { .. } => {
+                return Some(format!(
+                    "integers must be between {} and {}",
+                    i64::MIN,
+                    i64::MAX
+                ))
+            }
+            ParseError::InvalidEscape { .. } => {
+                return Some(String::from(
+                    "valid escapes are `\\n`, `\\t`, `\\\"`, `\\\\` and `\\u{...}`",
+                ))
+            }
+            ParseError::OutsideLoop { .. } => {
+                return Some(String::from(
+                    "a function body inside a loop is not part of the loop",
+                ))
+            }
+            ParseError::InvalidAssignmentTarget { .. } => {
+                return Some(String::from(
+                    "only variables and index expressions like `xs[0]` can be assigned to",
+                ))
+            }
+            ParseError::TooDeeplyNested { .. } => {
+                return Some(format!(
+                    "expressions can be nested at most {} levels deep",
+                    MAX_NESTING
+                ))
+            }
+            ParseError::Lex(error) => return error.hint(),
+            _ => return None,
         }
     }
+}
+
+impl From<LexError> for ParseError {
+    fn from(value: LexError) -> Self {
+        return ParseError::Lex(value);
+    }
+}
+
+fn describe(token: &TokenKind) -> String {
+    match token {
+        TokenKind::EOF => return String::from("end of input"),
+        TokenKind::IDENT => return String::from("an identifier"),
+        TokenKind::INT => return String::from("an integer"),
+        TokenKind::STRING => return String::from("a string"),
+        _ => return format!("`{}`", token),
+    }
+}
+
+impl<'src> Parser<'src> {
+    pub fn new(input: &'src str) -> Parser<'src> {
+        let mut parser = Parser {
+            lexer: Lexer::new(input),
+            cur_token: Token {
+                kind: TokenKind::EOF,
+                span: Span::default(),
+                literal: "",
+            },
+            next_token: Token {
+                kind: TokenKind::EOF,
+                span: Span::default(),
+                literal: "",
+            },
+            cur_doc: None,
+            next_doc: None,
+            errors: Vec::new(),
+            loop_depth: 0,
+            nesting: 0,
+        };
+
+        parser.advance_token();
+        parser.advance_token();
+
+        return parser;
+    }
+
+    /// Errors recorded by the last call to `parse_program`. Statements that
+    /// failed to parse are left out of the returned `Program`.
+    pub fn errors(&self) -> &[ParseError] {
+        return &self.errors;
+    }
 
     fn advance_token(&mut self) {
-        self.cur_token = self.next_token.clone();
+        self.cur_token = self.next_token;
+        self.cur_doc = self.next_doc.take();
         self.next_token = self.lexer.next_token();
+
+        // Illegal characters are reported by the lexer and doc comments are
+        // held on to for the token they precede, the grammar sees neither.
+        loop {
+            match self.next_token.kind {
+                TokenKind::ILLEGAL => {}
+                TokenKind::DOCCOMMENT => {
+                    let line = &self.next_token.literal[3..];
+                    let line = line.strip_prefix(' ').unwrap_or(line).trim_end();
+
+                    match &mut self.next_doc {
+                        Some(doc) => {
+                            doc.push('\n');
+                            doc.push_str(line);
+                        }
+                        None => self.next_doc = Some(line.to_string()),
+                    }
+                }
+                _ => break,
+            }
+
+            self.next_token = self.lexer.next_token();
+        }
+
+        let lex_errors = self.lexer.take_errors();
+        self.errors
+            .extend(lex_errors.into_iter().map(ParseError::from));
     }
 
     pub fn parse_program(&mut self) -> Program {
         let mut statements = Vec::new();
 
-        while self.cur_token != Token::EOF {
-            let statement = match self.cur_token {
-                Token::LET => match self.parse_let_statement() {
-                    Ok(let_statement) => let_statement,
-                    Err(e) => panic!("{}", e),
-                },
-                Token::RETURN => match self.parse_return_statement() {
-                    Ok(return_statement) => return_statement,
-                    Err(e) => panic!("{}", e),
-                },
-                _ => match self.parse_expression(self.precedence_of_cur_token()) {
-                    Ok(expresion) => Statement::Expression(expresion),
-                    Err(e) => panic!("{}", e),
-                },
-            };
+        while !self.cur_token_is(TokenKind::EOF) {
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize();
+                }
+            }
 
             self.advance_token();
-            statements.push(statement);
         }
 
-        return Program {
-            statements: statements,
-        };
+        return Program { statements };
+    }
+
+    fn parse_statement(&mut self) -> Result<Statement, ParseError> {
+        match self.cur_token.kind {
+            TokenKind::LET => return self.parse_let_statement(),
+            TokenKind::RETURN => return self.parse_return_statement(),
+            TokenKind::WHILE => return self.parse_while_statement(),
+            TokenKind::FOR => return self.parse_for_statement(),
+            TokenKind::BREAK | TokenKind::CONTINUE => return self.parse_loop_jump(),
+            _ => {
+                let expresion = self.parse_expression(Precedence::Lowest)?;
+                let span = expresion.span();
+                self.skip_semicolon();
+
+                return Ok(Statement::Expression(expresion, span));
+            }
+        }
+    }
+
+    fn skip_semicolon(&mut self) {
+        if self.next_token_is(TokenKind::SEMICOLON) {
+            self.advance_token();
+        }
+    }
+
+    /// Skips ahead to the end of the broken statement, a `;` or `}`, so
+    /// parsing can resume with the statement after it.
+    fn synchronize(&mut self) {
+        while !self.cur_token_is(TokenKind::SEMICOLON)
+            && !self.cur_token_is(TokenKind::RBRACE)
+            && !self.cur_token_is(TokenKind::EOF)
+        {
+            self.advance_token();
+        }
     }
 
     fn parse_let_statement(&mut self) -> Result<Statement, ParseError> {
+        let start = self.cur_token.span;
+        let doc = self.cur_doc.take();
         self.advance_token();
 
-        let identifier = match &self.cur_token {
-            Token::IDENT(string) => string.to_owned(),
+        let identifier = match self.cur_token.kind {
+            TokenKind::IDENT => {
+                Expresion::Identifer(self.cur_token.literal.to_string(), self.cur_token.span)
+            }
             _ => {
-                return Err(format!(
-                    "No identiefer found after let statement found: {:?}",
-                    self.cur_token
-                ))
+                return Err(ParseError::MissingIdentifier {
+                    found: self.cur_token.kind,
+                    span: self.cur_token.span,
+                })
             }
         };
 
         self.advance_token();
 
-        if !self.expect_peek(Token::ASSIGN) {
-            return Err(format!(
-                "No equal sign found after let identiefer found: {:?}",
-                self.cur_token
-            ));
+        if !self.expect_cur(TokenKind::ASSIGN) {
+            return Err(ParseError::MissingAssign {
+                found: self.cur_token.kind,
+                span: self.cur_token.span,
+            });
         }
 
-        let expression = self.parse_expression(self.precedence_of_cur_token())?;
+        let value = self.parse_expression(Precedence::Lowest)?;
+        let span = start.to(value.span());
+        self.skip_semicolon();
 
-        return Ok(Statement::Let(Expresion::Identifer(identifier), expression));
+        return Ok(Statement::Let {
+            name: identifier,
+            value,
+            doc,
+            span,
+        });
     }
 
     fn parse_return_statement(&mut self) -> Result<Statement, ParseError> {
+        let start = self.cur_token.span;
         self.advance_token();
 
-        if let Ok(expresion) = self.parse_expression(self.precedence_of_cur_token()) {
-            return Ok(Statement::Return(expresion));
-        } else {
-            return Err(format!(
-                "Failed to parse expression, found: {:?}",
-                self.cur_token
-            ));
-        };
+        let expresion = self.parse_expression(Precedence::Lowest)?;
+        let span = start.to(expresion.span());
+        self.skip_semicolon();
+
+        return Ok(Statement::Return(expresion, span));
     }
 
-    fn parse_expression(&mut self, precedence: Precedence) -> Result<Expresion, ParseError> {
-        let mut expression = match &self.cur_token {
-            Token::BANG => self.parse_prefix()?,
-            Token::MINUS => self.parse_prefix()?,
-            Token::IDENT(_) => self.parse_identifier()?,
-            Token::INT(_) => self.parse_integer()?,
-            Token::TRUE | Token::FALSE => self.parse_boolean()?,
-            _ => {
-                return Err(format!(
-                    "Non implemetned expression found {:?}",
-                    &self.cur_token
-                ))
-            }
-        };
+    fn parse_while_statement(&mut self) -> Result<Statement, ParseError> {
+        let start = self.cur_token.span;
 
-        if self.next_token_is(Token::SEMICOLON) {
-            self.advance_token();
-        }
+        self.expect_next(TokenKind::LPAREN)?;
+        self.advance_token();
 
-        while !self.cur_token_is(Token::SEMICOLON) && precedence < self.precedence_of_next_token() {
-            self.advance_token();
+        let condition = self.parse_expression(Precedence::Lowest)?;
 
-            expression = match &self.cur_token {
-                Token::LT
-                | Token::GT
-                | Token::EQ
-                | Token::NOTEQ
-                | Token::MINUS
-                | Token::PLUS
-                | Token::SLASH
-                | Token::ASTERISK => self.parse_infix(expression)?,
-                _ => return Ok(expression),
-            };
-        }
+        self.expect_next(TokenKind::RPAREN)?;
+        self.expect_next(TokenKind::LBRACE)?;
 
-        return Ok(expression);
+        let body = self.parse_loop_body()?;
+        let span = start.to(body.span);
+        self.skip_semicolon();
+
+        return Ok(Statement::While {
+            condition,
+            body,
+            span,
+        });
     }
 
-    fn parse_identifier(&self) -> Result<Expresion, ParseError> {
-        if let Token::IDENT(string) = &self.cur_token {
-            return Ok(Expresion::Identifer(string.to_owned()));
+    fn parse_for_statement(&mut self) -> Result<Statement, ParseError> {
+        let start = self.cur_token.span;
+
+        self.expect_next(TokenKind::LPAREN)?;
+        self.expect_next(TokenKind::IDENT)?;
+        let variable = self.parse_identifier()?;
+
+        self.expect_next(TokenKind::IN)?;
+        self.advance_token();
+        let iterable = self.parse_expression(Precedence::Lowest)?;
+
+        self.expect_next(TokenKind::RPAREN)?;
+        self.expect_next(TokenKind::LBRACE)?;
+
+        let body = self.parse_loop_body()?;
+        let span = start.to(body.span);
+        self.skip_semicolon();
+
+        return Ok(Statement::For {
+            variable,
+            iterable,
+            body,
+            span,
+        });
+    }
+
+    fn parse_loop_body(&mut self) -> Result<BlockStatement, ParseError> {
+        self.loop_depth += 1;
+        let body = self.parse_block();
+        self.loop_depth -= 1;
+
+        return body;
+    }
+
+    /// Parses `break` or `continue`.
+    fn parse_loop_jump(&mut self) -> Result<Statement, ParseError> {
+        let keyword = self.cur_token.kind;
+        let span = self.cur_token.span;
+
+        if self.loop_depth == 0 {
+            return Err(ParseError::OutsideLoop { keyword, span });
         }
 
-        return Err(format!(
-            "No Identifier found whilst trying to parse identiefer found: {:?}",
-            self.cur_token
-        ));
+        self.skip_semicolon();
+
+        match keyword {
+            TokenKind::BREAK => return Ok(Statement::Break(span)),
+            _ => return Ok(Statement::Continue(span)),
+        }
     }
 
-    fn parse_integer(&self) -> Result<Expresion, ParseError> {
-        if let Token::INT(num_literal) = &self.cur_token {
-            let num = match num_literal.to_owned().parse() {
-                Ok(parsed_num) => Ok(Expresion::Interger(parsed_num)),
-                Err(_) => Err(format!(
-                    "Failed to parse number into Interger found: {:?}",
-                    self.cur_token
-                )),
-            };
+    fn parse_expression(&mut self, precedence: Precedence) -> Result<Expresion, ParseError> {
+        // Restored here so a failed expression does not leave its nesting
+        // behind for the statements parsed after it.
+        let nesting = self.nesting;
+        let expression = self.parse_nested(precedence);
+        self.nesting = nesting;
 
-            return num;
+        return expression;
+    }
+
+    fn parse_nested(&mut self, precedence: Precedence) -> Result<Expresion, ParseError> {
+        self.nest()?;
+        let mut expression = self.parse_leading()?;
+
+        while !self.next_token_is(TokenKind::SEMICOLON)
+            && precedence < self.precedence_of_next_token()
+        {
+            self.advance_token();
+
+            // `1 + 1 + 1` parses in this loop rather than by recursing, but
+            // each operator still wraps the expression in another node.
+            self.nest()?;
+            expression = self.parse_trailing(expression)?;
         }
 
-        return Err(format!(
-            "No integer found whilst trying to parse integer found: {:?}",
-            self.cur_token
-        ));
+        return Ok(expression);
     }
 
-    fn parse_operator(&self) -> Operator {
-        return Operator::from(self.cur_token.clone());
+    fn nest(&mut self) -> Result<(), ParseError> {
+        self.nesting += 1;
+
+        if self.nesting > MAX_NESTING {
+            return Err(ParseError::TooDeeplyNested {
+                span: self.cur_token.span,
+            });
+        }
+
+        return Ok(());
     }
 
-    fn parse_boolean(&self) -> Result<Expresion, ParseError> {
-        match self.cur_token {
-            Token::TRUE => return Ok(Expresion::Bool { value: true }),
-            Token::FALSE => return Ok(Expresion::Bool { value: false }),
+    /// Parses the expression that starts at the current token. Every arm
+    /// returns straight away, so recursing through here stays cheap on the
+    /// native stack.
+    fn parse_leading(&mut self) -> Result<Expresion, ParseError> {
+        match self.cur_token.kind {
+            TokenKind::BANG | TokenKind::MINUS => return self.parse_prefix(),
+            TokenKind::IDENT => return self.parse_identifier(),
+            TokenKind::INT => return self.parse_integer(),
+            TokenKind::STRING => return self.parse_string(),
+            TokenKind::TRUE | TokenKind::FALSE => return self.parse_boolean(),
+            TokenKind::LPAREN => return self.parse_grouped(),
+            TokenKind::IF => return self.parse_if(),
+            TokenKind::FUNCTION => return self.parse_function(),
+            TokenKind::LBRACKET => return self.parse_array(),
+            // Blocks are only parsed right after `if`, `else` and `fn(..)`,
+            // so a brace in expression position always opens a hash.
+            TokenKind::LBRACE => return self.parse_hash(),
             _ => {
-                return Err(format!(
-                    "Expected either True or False token in parse_boolean got: {:?}",
-                    self.cur_token
-                ))
+                return Err(ParseError::ExpectedExpression {
+                    found: self.cur_token.kind,
+                    span: self.cur_token.span,
+                })
             }
         }
     }
 
-    fn parse_prefix(&mut self) -> Result<Expresion, ParseError> {
-        let left = self.parse_operator();
+    /// Parses the operator at the current token, with `left` as its left
+    /// hand side.
+    fn parse_trailing(&mut self, left: Expresion) -> Result<Expresion, ParseError> {
+        match self.cur_token.kind {
+            TokenKind::LPAREN => return self.parse_call(left),
+            TokenKind::LBRACKET => return self.parse_index(left),
+            TokenKind::ASSIGN
+            | TokenKind::PLUSASSIGN
+            | TokenKind::MINUSASSIGN
+            | TokenKind::ASTERISKASSIGN
+            | TokenKind::SLASHASSIGN => return self.parse_assign(left),
+            // Every other token with a precedence is a binary operator.
+            _ => return self.parse_infix(left),
+        }
+    }
 
+    fn parse_grouped(&mut self) -> Result<Expresion, ParseError> {
+        let start = self.cur_token.span;
         self.advance_token();
 
-        if let Ok(right) = self.parse_expression(self.precedence_of_cur_token()) {
-            return Ok(Expresion::Prefix(left, Box::new(right)));
-        } else {
-            return Err(format!(
-                "Failed to parse prefix, found: {:?}",
-                self.cur_token
-            ));
-        }
+        let mut expression = self.parse_expression(Precedence::Lowest)?;
+        self.expect_next(TokenKind::RPAREN)?;
+
+        // Widen the span so it covers the parentheses.
+        *expression.span_mut() = start.to(self.cur_token.span);
+
+        return Ok(expression);
     }
 
-    fn parse_infix(&mut self, left: Expresion) -> Result<Expresion, ParseError> {
-        let operator = self.parse_operator();
-        let precedence = self.precedence_of_cur_token();
+    fn parse_if(&mut self) -> Result<Expresion, ParseError> {
+        let start = self.cur_token.span;
 
+        self.expect_next(TokenKind::LPAREN)?;
         self.advance_token();
 
-        if let Ok(right) = self.parse_expression(precedence) {
-            return Ok(Expresion::Infix(Box::new(left), operator, Box::new(right)));
-        } else {
-            return Err(format!("Failed to parse infix found: {:?}", self.cur_token));
-        }
-    }
+        let condition = self.parse_expression(Precedence::Lowest)?;
 
-    fn cur_token_is(&self, token: Token) -> bool {
-        return &self.cur_token == &token;
-    }
+        self.expect_next(TokenKind::RPAREN)?;
+        self.expect_next(TokenKind::LBRACE)?;
 
-    fn next_token_is(&self, token: Token) -> bool {
-        return &self.next_token == &token;
-    }
+        let consequence = self.parse_block()?;
+        let mut alternative = None;
 
-    fn expect_peek(&mut self, token: Token) -> bool {
-        if self.cur_token_is(token) {
+        if self.next_token_is(TokenKind::ELSE) {
             self.advance_token();
-            return true;
-        } else {
-            return false;
+
+            if self.next_token_is(TokenKind::IF) {
+                self.advance_token();
+
+                let nested = self.parse_if()?;
+                let span = nested.span();
+
+                alternative = Some(BlockStatement {
+                    statements: vec![Statement::Expression(nested, span)],
+                    span,
+                });
+            } else {
+                self.expect_next(TokenKind::LBRACE)?;
+                alternative = Some(self.parse_block()?);
+            }
         }
-    }
 
-    fn precedence_of_cur_token(&self) -> Precedence {
-        return Precedence::from(self.cur_token.clone());
-    }
+        let end = alternative.as_ref().unwrap_or(&consequence).span;
 
-    fn precedence_of_next_token(&self) -> Precedence {
-        return Precedence::from(self.next_token.clone());
+        return Ok(Expresion::If {
+            condition: Box::new(condition),
+            consequence,
+            alternative,
+            span: start.to(end),
+        });
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::Parser;
-    use crate::ast::{Expresion, Operator, Program, Statement};
+    fn parse_function(&mut self) -> Result<Expresion, ParseError> {
+        let start = self.cur_token.span;
 
-    #[test]
-    fn test_let_statement_parse_program() {
-        let input = "
-        let x = 5;
-        let y = 10;
-        let foobar = 838383;
-        "
-        .to_string();
+        self.expect_next(TokenKind::LPAREN)?;
+        let params = self.parse_function_params()?;
 
-        let mut parser = Parser::new(input);
-        let program = parser.parse_program();
+        self.expect_next(TokenKind::LBRACE)?;
 
-        assert_eq!(3, program.statements.len());
+        // Loops around the function do not reach into its body.
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        let body = self.parse_block();
+        self.loop_depth = loop_depth;
 
-        let expected_identifiers = vec![
-            Statement::Let(
-                Expresion::Identifer(String::from("x")),
-                Expresion::Interger(5),
-            ),
-            Statement::Let(
-                Expresion::Identifer(String::from("y")),
-                Expresion::Interger(10),
-            ),
-            Statement::Let(
-                Expresion::Identifer(String::from("foobar")),
-                Expresion::Interger(838383),
-            ),
-        ];
+        let body = body?;
+        let span = start.to(body.span);
 
-        for i in 0..=expected_identifiers.len() - 1 {
-            assert_eq!(program.statements[i], expected_identifiers[i]);
-        }
+        return Ok(Expresion::Function {
+            params,
+            body: Rc::new(body),
+            span,
+        });
     }
 
-    #[test]
-    fn test_return_statement_parse_program() {
-        let input = "
-        return 5;
-        return 10;
-        return 993322;
-        return 2 + 5;
-        "
-        .to_string();
-
-        let mut parser = Parser::new(input);
-        let program = parser.parse_program();
+    fn parse_function_params(&mut self) -> Result<Vec<Expresion>, ParseError> {
+        let mut params = Vec::new();
 
-        assert_eq!(4, program.statements.len());
+        if self.next_token_is(TokenKind::RPAREN) {
+            self.advance_token();
+            return Ok(params);
+        }
 
-        let expected_statements = vec![
-            Statement::Return(Expresion::Interger(5)),
-            Statement::Return(Expresion::Interger(10)),
-            Statement::Return(Expresion::Interger(993322)),
-            Statement::Return(Expresion::Infix(
-                Box::from(Expresion::Interger(2)),
-                Operator::Plus,
-                Box::from(Expresion::Interger(5)),
-            )),
-        ];
+        self.expect_next(TokenKind::IDENT)?;
+        params.push(self.parse_identifier()?);
 
-        for i in 0..=expected_statements.len() - 1 {
-            assert_eq!(program.statements[i], expected_statements[i]);
+        while self.next_token_is(TokenKind::COMMA) {
+            self.advance_token();
+            self.expect_next(TokenKind::IDENT)?;
+            params.push(self.parse_identifier()?);
         }
-    }
 
-    #[test]
-    fn test_expression_statement_parse_program() {
-        let input = "foobar;
-        5;"
-        .to_string();
+        self.expect_next(TokenKind::RPAREN)?;
 
-        let program = Parser::new(input).parse_program();
+        return Ok(params);
+    }
 
-        let expected_statements = vec![
-            Statement::Expression(Expresion::Identifer(String::from("foobar"))),
-            Statement::Expression(Expresion::Interger(5)),
-        ];
+    fn parse_call(&mut self, function: Expresion) -> Result<Expresion, ParseError> {
+        let args = self.parse_expression_list(TokenKind::RPAREN)?;
+        let span = function.span().to(self.cur_token.span);
 
-        assert_eq!(expected_statements.len(), program.statements.len());
+        return Ok(Expresion::Call {
+            function: Box::new(function),
+            args,
+            span,
+        });
+    }
 
-        for i in 0..=expected_statements.len() - 1 {
-            assert_eq!(program.statements[i], expected_statements[i]);
-        }
+    fn parse_array(&mut self) -> Result<Expresion, ParseError> {
+        let start = self.cur_token.span;
+        let elements = self.parse_expression_list(TokenKind::RBRACKET)?;
+
+        return Ok(Expresion::Array(elements, start.to(self.cur_token.span)));
     }
 
-    #[test]
-    fn test_prefix_expression_parse_program() {
-        let input = "-5;
-        !foobar;"
-            .to_string();
+    fn parse_hash(&mut self) -> Result<Expresion, ParseError> {
+        let start = self.cur_token.span;
+        let mut pairs = Vec::new();
 
-        let program = Parser::new(input).parse_program();
+        while !self.next_token_is(TokenKind::RBRACE) {
+            self.advance_token();
+            let key = self.parse_expression(Precedence::Lowest)?;
 
-        let expected_statements = vec![
-            Statement::Expression(Expresion::Prefix(
-                Operator::Minus,
-                Box::new(Expresion::Interger(5)),
-            )),
-            Statement::Expression(Expresion::Prefix(
-                Operator::Not,
-                Box::new(Expresion::Identifer(String::from("foobar"))),
-            )),
-        ];
+            self.expect_next(TokenKind::COLON)?;
+            self.advance_token();
+            let value = self.parse_expression(Precedence::Lowest)?;
 
-        assert_eq!(expected_statements.len(), program.statements.len());
+            pairs.push((key, value));
 
-        for i in 0..=expected_statements.len() - 1 {
-            assert_eq!(program.statements[i], expected_statements[i]);
+            if !self.next_token_is(TokenKind::RBRACE) {
+                self.expect_next(TokenKind::COMMA)?;
+            }
         }
+
+        self.advance_token();
+
+        return Ok(Expresion::Hash(pairs, start.to(self.cur_token.span)));
     }
 
-    #[test]
-    fn test_infix_expression_parse_program() {
-        let input = "5 + 5;
-        5 - 5;
-        5 * 5;
-        5 / 5;
-        5 > 5;
-        5 < 5;
-        5 != 5;
-        5 == 5;"
-            .to_string();
+    fn parse_index(&mut self, left: Expresion) -> Result<Expresion, ParseError> {
+        self.advance_token();
 
-        let program = Parser::new(input).parse_program();
+        let index = self.parse_expression(Precedence::Lowest)?;
+        self.expect_next(TokenKind::RBRACKET)?;
 
-        let expected_statements = vec![
-            Statement::Expression(Expresion::Infix(
-                Box::new(Expresion::Interger(5)),
-                Operator::Plus,
-                Box::new(Expresion::Interger(5)),
-            )),
-            Statement::Expression(Expresion::Infix(
-                Box::new(Expresion::Interger(5)),
-                Operator::Minus,
-                Box::new(Expresion::Interger(5)),
-            )),
-            Statement::Expression(Expresion::Infix(
-                Box::new(Expresion::Interger(5)),
-                Operator::Multiply,
-                Box::new(Expresion::Interger(5)),
-            )),
-            Statement::Expression(Expresion::Infix(
-                Box::new(Expresion::Interger(5)),
-                Operator::Divide,
-                Box::new(Expresion::Interger(5)),
-            )),
-            Statement::Expression(Expresion::Infix(
-                Box::new(Expresion::Interger(5)),
-                Operator::Gt,
-                Box::new(Expresion::Interger(5)),
-            )),
-            Statement::Expression(Expresion::Infix(
-                Box::new(Expresion::Interger(5)),
-                Operator::Lt,
-                Box::new(Expresion::Interger(5)),
-            )),
-            Statement::Expression(Expresion::Infix(
-                Box::new(Expresion::Interger(5)),
-                Operator::NotEquals,
-                Box::new(Expresion::Interger(5)),
-            )),
-            Statement::Expression(Expresion::Infix(
-                Box::new(Expresion::Interger(5)),
-                Operator::Equals,
-                Box::new(Expresion::Interger(5)),
-            )),
-        ];
+        let span = left.span().to(self.cur_token.span);
 
-        assert_eq!(expected_statements.len(), program.statements.len());
+        return Ok(Expresion::Index {
+            left: Box::new(left),
+            index: Box::new(index),
+            span,
+        });
+    }
 
-        for i in 0..=expected_statements.len() - 1 {
-            assert_eq!(program.statements[i], expected_statements[i]);
+    /// Parses comma separated expressions up to and including `end`. The
+    /// current token is the opening delimiter.
+    fn parse_expression_list(&mut self, end: TokenKind) -> Result<Vec<Expresion>, ParseError> {
+        let mut list = Vec::new();
+
+        if self.next_token_is(end) {
+            self.advance_token();
+            return Ok(list);
+        }
+
+        self.advance_token();
+        list.push(self.parse_expression(Precedence::Lowest)?);
+
+        while self.next_token_is(TokenKind::COMMA) {
+            self.advance_token();
+            self.advance_token();
+            list.push(self.parse_expression(Precedence::Lowest)?);
         }
+
+        self.expect_next(end)?;
+
+        return Ok(list);
     }
 
-    #[test]
-    fn test_parse_boolean() {
-        let input = "true;
-        false;
-        let foobar = true;
-        let barfoo = false;"
-            .to_string();
-        let program = Parser::new(input).parse_program();
+    /// Parses the statements between `{` and `}`. Broken statements are
+    /// recorded and skipped like in `parse_program`, so one typo in a block
+    /// does not hide errors further down.
+    fn parse_block(&mut self) -> Result<BlockStatement, ParseError> {
+        let start = self.cur_token.span;
+        let mut statements = Vec::new();
 
-        let expected_statements = vec![
-            Statement::Expression(Expresion::Bool { value: true }),
-            Statement::Expression(Expresion::Bool { value: false }),
-            Statement::Let(
-                Expresion::Identifer(String::from("foobar")),
-                Expresion::Bool { value: true },
-            ),
-            Statement::Let(
-                Expresion::Identifer(String::from("barfoo")),
-                Expresion::Bool { value: false },
-            ),
-        ];
+        self.advance_token();
 
-        assert_eq!(expected_statements.len(), program.statements.len());
+        while !self.cur_token_is(TokenKind::RBRACE) {
+            if self.cur_token_is(TokenKind::EOF) {
+                return Err(ParseError::UnexpectedToken {
+                    expected: TokenKind::RBRACE,
+                    found: TokenKind::EOF,
+                    span: self.cur_token.span,
+                });
+            }
 
-        for i in 0..=expected_statements.len() - 1 {
-            assert_eq!(program.statements[i], expected_statements[i]);
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize();
+
+                    if !self.cur_token_is(TokenKind::SEMICOLON) {
+                        continue;
+                    }
+                }
+            }
+
+            self.advance_token();
         }
+
+        return Ok(BlockStatement {
+            statements,
+            span: start.to(self.cur_token.span),
+        });
+    }
+
+    fn parse_identifier(&self) -> Result<Expresion, ParseError> {
+        if self.cur_token_is(TokenKind::IDENT) {
+            return Ok(Expresion::Identifer(
+                self.cur_token.literal.to_string(),
+                self.cur_token.span,
+            ));
+        }
+
+        return Err(ParseError::MissingIdentifier {
+            found: self.cur_token.kind,
+            span: self.cur_token.span,
+        });
+    }
+
+    fn parse_integer(&self) -> Result<Expresion, ParseError> {
+        if self.cur_token_is(TokenKind::INT) {
+            let num_literal = self.cur_token.literal;
+            let num = match num_literal.parse() {
+                Ok(parsed_num) => Ok(Expresion::Interger(parsed_num, self.cur_token.span)),
+                Err(_) => Err(ParseError::InvalidInteger {
+                    literal: num_literal.to_owned(),
+                    span: self.cur_token.span,
+                }),
+            };
+
+            return num;
+        }
+
+        return Err(ParseError::InvalidInteger {
+            literal: self.cur_token.literal.to_string(),
+            span: self.cur_token.span,
+        });
+    }
+
+    fn parse_string(&self) -> Result<Expresion, ParseError> {
+        let token = self.cur_token;
+        // The lexer only emits terminated strings, strip the quotes.
+        let raw = &token.literal[1..token.literal.len() - 1];
+        let mut value = String::with_capacity(raw.len());
+        let mut chars = raw.char_indices().peekable();
+
+        while let Some((start, ch)) = chars.next() {
+            if ch != '\\' {
+                value.push(ch);
+                continue;
+            }
+
+            let escaped = match chars.next() {
+                Some((_, 'n')) => Some('\n'),
+                Some((_, 't')) => Some('\t'),
+                Some((_, '"')) => Some('"'),
+                Some((_, '\\')) => Some('\\'),
+                Some((_, 'u')) => read_unicode_escape(&mut chars),
+                _ => None,
+            };
+
+            match escaped {
+                Some(ch) => value.push(ch),
+                None => {
+                    let end = chars.peek().map_or(raw.len(), |(i, _)| *i);
+
+                    return Err(ParseError::InvalidEscape {
+                        sequence: raw[start..end].to_string(),
+                        span: token.span.sub_span(token.literal, start + 1, end + 1),
+                    });
+                }
+            }
+        }
+
+        return Ok(Expresion::String(value, token.span));
+    }
+
+    fn parse_operator(&self) -> Operator {
+        return Operator::from(&self.cur_token.kind);
+    }
+
+    fn parse_boolean(&self) -> Result<Expresion, ParseError> {
+        let span = self.cur_token.span;
+
+        match self.cur_token.kind {
+            TokenKind::TRUE => return Ok(Expresion::Bool { value: true, span }),
+            TokenKind::FALSE => return Ok(Expresion::Bool { value: false, span }),
+            _ => {
+                return Err(ParseError::UnexpectedToken {
+                    expected: TokenKind::TRUE,
+                    found: self.cur_token.kind,
+                    span,
+                })
+            }
+        }
+    }
+
+    fn parse_prefix(&mut self) -> Result<Expresion, ParseError> {
+        let left = self.parse_operator();
+        let start = self.cur_token.span;
+
+        self.advance_token();
+
+        // `-9223372036854775808` only fits an i64 with its minus sign, so it
+        // is read as a single negative literal instead of a negation.
+        if left == Operator::Minus
+            && self.cur_token_is(TokenKind::INT)
+            && self.cur_token.literal.parse::<i64>().is_err()
+        {
+            if let Ok(value) = format!("-{}", self.cur_token.literal).parse() {
+                return Ok(Expresion::Interger(value, start.to(self.cur_token.span)));
+            }
+        }
+
+        let right = self.parse_expression(Precedence::Prefix)?;
+        let span = start.to(right.span());
+
+        return Ok(Expresion::Prefix(left, Box::new(right), span));
+    }
+
+    fn parse_infix(&mut self, left: Expresion) -> Result<Expresion, ParseError> {
+        let operator = self.parse_operator();
+
+        // `**` is right associative, parsing its right side a level lower
+        // lets a `**` that follows bind to it first.
+        let precedence = match operator {
+            Operator::Power => Precedence::Prefix,
+            _ => self.precedence_of_cur_token(),
+        };
+
+        self.advance_token();
+
+        let right = self.parse_expression(precedence)?;
+        let span = left.span().to(right.span());
+
+        return Ok(Expresion::Infix(
+            Box::new(left),
+            operator,
+            Box::new(right),
+            span,
+        ));
+    }
+
+    fn parse_assign(&mut self, target: Expresion) -> Result<Expresion, ParseError> {
+        if !is_assignable(&target) {
+            return Err(ParseError::InvalidAssignmentTarget {
+                span: target.span(),
+            });
+        }
+
+        let operator = match self.cur_token.kind {
+            TokenKind::ASSIGN => None,
+            _ => Some(self.parse_operator()),
+        };
+
+        self.advance_token();
+
+        // Parsing the value at the lowest precedence makes `a = b = 1`
+        // assign right to left.
+        let value = self.parse_expression(Precedence::Lowest)?;
+        let span = target.span().to(value.span());
+
+        return Ok(Expresion::Assign {
+            target: Box::new(target),
+            operator,
+            value: Box::new(value),
+            span,
+        });
+    }
+
+    fn cur_token_is(&self, token: TokenKind) -> bool {
+        return self.cur_token.kind == token;
+    }
+
+    fn next_token_is(&self, token: TokenKind) -> bool {
+        return self.next_token.kind == token;
+    }
+
+    fn expect_next(&mut self, token: TokenKind) -> Result<(), ParseError> {
+        if self.next_token_is(token) {
+            self.advance_token();
+            return Ok(());
+        }
+
+        return Err(ParseError::UnexpectedToken {
+            expected: token,
+            found: self.next_token.kind,
+            span: self.next_token.span,
+        });
+    }
+
+    fn expect_cur(&mut self, token: TokenKind) -> bool {
+        if self.cur_token_is(token) {
+            self.advance_token();
+            return true;
+        } else {
+            return false;
+        }
+    }
+
+    fn precedence_of_cur_token(&self) -> Precedence {
+        return Precedence::from(&self.cur_token.kind);
+    }
+
+    fn precedence_of_next_token(&self) -> Precedence {
+        return Precedence::from(&self.next_token.kind);
+    }
+}
+
+/// A variable, or an index expression into one at any depth.
+fn is_assignable(expression: &Expresion) -> bool {
+    match expression {
+        Expresion::Identifer(..) => return true,
+        Expresion::Index { left, .. } => return is_assignable(left),
+        _ => return false,
+    }
+}
+
+/// Reads the `{1F600}` part of a `\u{1F600}` escape, `None` if it is malformed.
+fn read_unicode_escape(chars: &mut Peekable<CharIndices>) -> Option<char> {
+    chars.next_if(|(_, ch)| *ch == '{')?;
+
+    let mut code: u32 = 0;
+    let mut digits = 0;
+
+    while let Some((_, ch)) = chars.next_if(|(_, ch)| *ch != '}') {
+        code = code.checked_mul(16)?.checked_add(ch.to_digit(16)?)?;
+        digits += 1;
+    }
+
+    chars.next_if(|(_, ch)| *ch == '}')?;
+
+    if digits == 0 || digits > 6 {
+        return None;
+    }
+
+    return char::from_u32(code);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParseError, Parser, MAX_NESTING};
+    use crate::{
+        ast::{Expresion, Operator, Statement},
+        lexer::LexError,
+        span::Span,
+        token::TokenKind,
+    };
+
+    #[test]
+    fn test_let_statement_parse_program() {
+        let input = "
+        let x = 5;
+        let y = 10;
+        let foobar = 838383;
+        ";
+
+        let mut parser = Parser::new(input);
+        let program = parser.parse_program();
+
+        assert_eq!(3, program.statements.len());
+
+        let expected_identifiers = vec!["let x = 5;", "let y = 10;", "let foobar = 838383;"];
+
+        for i in 0..=expected_identifiers.len() - 1 {
+            assert_eq!(program.statements[i].to_string(), expected_identifiers[i]);
+        }
+    }
+
+    #[test]
+    fn test_return_statement_parse_program() {
+        let input = "
+        return 5;
+        return 10;
+        return 993322;
+        return 2 + 5;
+        ";
+
+        let mut parser = Parser::new(input);
+        let program = parser.parse_program();
+
+        assert_eq!(4, program.statements.len());
+
+        let expected_statements = vec![
+            "return 5;",
+            "return 10;",
+            "return 993322;",
+            "return (2 + 5);",
+        ];
+
+        for i in 0..=expected_statements.len() - 1 {
+            assert!(matches!(program.statements[i], Statement::Return(..)));
+            assert_eq!(program.statements[i].to_string(), expected_statements[i]);
+        }
+    }
+
+    #[test]
+    fn test_expression_statement_parse_program() {
+        let input = "foobar;
+        5;";
+
+        let program = Parser::new(input).parse_program();
+
+        assert_eq!(2, program.statements.len());
+        assert!(matches!(
+            &program.statements[0],
+            Statement::Expression(Expresion::Identifer(name, _), _) if name == "foobar"
+        ));
+        assert!(matches!(
+            &program.statements[1],
+            Statement::Expression(Expresion::Interger(5, _), _)
+        ));
+    }
+
+    #[test]
+    fn test_prefix_expression_parse_program() {
+        let input = "-5;
+        !foobar;";
+
+        let program = Parser::new(input).parse_program();
+
+        let expected_statements = vec![(Operator::Minus, "(-5)"), (Operator::Not, "(!foobar)")];
+
+        assert_eq!(expected_statements.len(), program.statements.len());
+
+        for i in 0..=expected_statements.len() - 1 {
+            let (operator, expected) = expected_statements[i];
+
+            assert!(matches!(
+                &program.statements[i],
+                Statement::Expression(Expresion::Prefix(op, _, _), _) if *op == operator
+            ));
+            assert_eq!(program.statements[i].to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_infix_expression_parse_program() {
+        let input = "5 + 5;
+        5 - 5;
+        5 * 5;
+        5 / 5;
+        5 > 5;
+        5 < 5;
+        5 != 5;
+        5 == 5;";
+
+        let program = Parser::new(input).parse_program();
+
+        let expected_operators = vec![
+            Operator::Plus,
+            Operator::Minus,
+            Operator::Multiply,
+            Operator::Divide,
+            Operator::Gt,
+            Operator::Lt,
+            Operator::NotEquals,
+            Operator::Equals,
+        ];
+
+        assert_eq!(expected_operators.len(), program.statements.len());
+
+        for i in 0..=expected_operators.len() - 1 {
+            let operator = expected_operators[i];
+
+            assert!(matches!(
+                &program.statements[i],
+                Statement::Expression(Expresion::Infix(left, op, right, _), _)
+                    if **left == Expresion::Interger(5, left.span())
+                        && *op == operator
+                        && **right == Expresion::Interger(5, right.span())
+            ));
+            assert_eq!(
+                program.statements[i].to_string(),
+                format!("(5 {} 5)", operator)
+            );
+        }
+    }
+
+    #[test]
+    fn test_operator_precedence_parse_program() {
+        let tests = vec![
+            ("-a * b", "((-a) * b)"),
+            ("!-a", "(!(-a))"),
+            ("-a + b", "((-a) + b)"),
+            ("a + b + c", "((a + b) + c)"),
+            ("a + b - c", "((a + b) - c)"),
+            ("a * b * c", "((a * b) * c)"),
+            ("a * b / c", "((a * b) / c)"),
+            ("a + b / c", "(a + (b / c))"),
+            ("a + b * c + d / e - f", "(((a + (b * c)) + (d / e)) - f)"),
+            ("5 > 4 == 3 < 4", "((5 > 4) == (3 < 4))"),
+            (
+                "3 + 4 * 5 == 3 * 1 + 4 * 5",
+                "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))",
+            ),
+            ("3 > 5 == false", "((3 > 5) == false)"),
+            ("1 + (2 + 3) + 4", "((1 + (2 + 3)) + 4)"),
+            ("(5 + 5) * 2", "((5 + 5) * 2)"),
+            ("2 / (5 + 5)", "(2 / (5 + 5))"),
+            ("-(5 + 5)", "(-(5 + 5))"),
+            ("!(true == true)", "(!(true == true))"),
+            ("((1 + (2 * 3)) * 4)", "((1 + (2 * 3)) * 4)"),
+            ("(((a)))", "a"),
+            ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
+            (
+                "add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))",
+                "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))",
+            ),
+            (
+                "add(a + b + c * d / f + g)",
+                "add((((a + b) + ((c * d) / f)) + g))",
+            ),
+            ("-f(x)", "(-f(x))"),
+            (
+                "a * [1, 2, 3, 4][b * c] * d",
+                "((a * ([1, 2, 3, 4][(b * c)])) * d)",
+            ),
+            (
+                "add(a * b[2], b[1], 2 * [1, 2][1])",
+                "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))",
+            ),
+            ("-a[0]", "(-(a[0]))"),
+            ("f(x)[0](y)", "(f(x)[0])(y)"),
+            ("a[0][1]", "((a[0])[1])"),
+        ];
+
+        for (input, expected) in tests {
+            let program = Parser::new(input).parse_program();
+
+            assert_eq!(program.statements.len(), 1, "{}", input);
+            assert_eq!(program.statements[0].to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_boolean() {
+        let input = "true;
+        false;
+        let foobar = true;
+        let barfoo = false;";
+        let program = Parser::new(input).parse_program();
+
+        let expected_values = vec![true, false, true, false];
+
+        assert_eq!(expected_values.len(), program.statements.len());
+
+        for i in 0..=expected_values.len() - 1 {
+            let expresion = match &program.statements[i] {
+                Statement::Expression(expresion, _) => expresion,
+                Statement::Let {
+                    value: expresion, ..
+                } => expresion,
+                statement => panic!("Unexpected statement {:?}", statement),
+            };
+
+            assert!(matches!(
+                expresion,
+                Expresion::Bool { value, .. } if *value == expected_values[i]
+            ));
+        }
+    }
+
+    #[test]
+    fn test_node_spans() {
+        let input = "let total = 1 + 23;
+return -x;";
+
+        let program = Parser::new(input).parse_program();
+
+        assert_eq!(program.statements.len(), 2);
+
+        let Statement::Let {
+            name: identifier,
+            value,
+            span,
+            ..
+        } = &program.statements[0]
+        else {
+            panic!("Expected let statement got {:?}", program.statements[0]);
+        };
+        assert_eq!(*span, Span::new(0, 18, 1, 1));
+        assert_eq!(identifier.span(), Span::new(4, 9, 1, 5));
+        assert_eq!(value.span(), Span::new(12, 18, 1, 13));
+
+        let Expresion::Infix(left, _, right, _) = value else {
+            panic!("Expected infix expression got {:?}", value);
+        };
+        assert_eq!(left.span(), Span::new(12, 13, 1, 13));
+        assert_eq!(right.span(), Span::new(16, 18, 1, 17));
+
+        let Statement::Return(value, span) = &program.statements[1] else {
+            panic!("Expected return statement got {:?}", program.statements[1]);
+        };
+        assert_eq!(*span, Span::new(20, 29, 2, 1));
+        assert_eq!(value.span(), Span::new(27, 29, 2, 8));
+    }
+
+    #[test]
+    fn test_parse_errors_are_collected() {
+        let input = "let x 5;
+        let = 10;
+        let y = 15;
+        5 + ;
+        y;";
+
+        let mut parser = Parser::new(input);
+        let program = parser.parse_program();
+
+        assert_eq!(parser.errors().len(), 3, "{:?}", parser.errors());
+
+        let expected_statements = vec!["let y = 15;", "y"];
+
+        assert_eq!(expected_statements.len(), program.statements.len());
+
+        for i in 0..=expected_statements.len() - 1 {
+            assert_eq!(program.statements[i].to_string(), expected_statements[i]);
+        }
+    }
+
+    #[test]
+    fn test_parse_error_recovers_at_closing_brace() {
+        let input = "} let a = 1; -";
+
+        let mut parser = Parser::new(input);
+        let program = parser.parse_program();
+
+        assert_eq!(parser.errors().len(), 2, "{:?}", parser.errors());
+        assert_eq!(program.statements.len(), 1);
+        assert_eq!(program.statements[0].to_string(), "let a = 1;");
+    }
+
+    #[test]
+    fn test_valid_program_has_no_errors() {
+        let mut parser = Parser::new("let a = 1; a + 2;");
+        parser.parse_program();
+
+        assert!(parser.errors().is_empty());
+    }
+
+    #[test]
+    fn test_structured_parse_errors() {
+        let input = "let 5 = 1;
+let x 2;
+return ;
+let big = 99999999999999999999;
+1 @ 2;";
+
+        let mut parser = Parser::new(input);
+        parser.parse_program();
+
+        let expected_errors = vec![
+            ParseError::MissingIdentifier {
+                found: TokenKind::INT,
+                span: Span::new(4, 5, 1, 5),
+            },
+            ParseError::MissingAssign {
+                found: TokenKind::INT,
+                span: Span::new(17, 18, 2, 7),
+            },
+            ParseError::ExpectedExpression {
+                found: TokenKind::SEMICOLON,
+                span: Span::new(27, 28, 3, 8),
+            },
+            ParseError::InvalidInteger {
+                literal: String::from("99999999999999999999"),
+                span: Span::new(39, 59, 4, 11),
+            },
+            ParseError::Lex(LexError::IllegalCharacter {
+                found: '@',
+                span: Span::new(63, 64, 5, 3),
+            }),
+        ];
+
+        assert_eq!(parser.errors(), expected_errors);
+    }
+
+    #[test]
+    fn test_illegal_character_is_skipped_by_parser() {
+        let mut parser = Parser::new("let a = 1 @+ 2;");
+        let program = parser.parse_program();
+
+        assert_eq!(parser.errors().len(), 1);
+        assert_eq!(program.statements[0].to_string(), "let a = (1 + 2);");
+    }
+
+    #[test]
+    fn test_parse_string_escapes() {
+        let tests = vec![
+            (r#""hello world""#, "hello world"),
+            (r#""line\nbreak""#, "line\nbreak"),
+            (r#""tab\tstop""#, "tab\tstop"),
+            (r#""say \"hi\"""#, "say \"hi\""),
+            (r#""back\\slash""#, "back\\slash"),
+            (r#""\u{48}\u{e9}\u{1F600}""#, "Hé😀"),
+            (r#""""#, ""),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(input);
+            let program = parser.parse_program();
+
+            assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+            assert!(matches!(
+                &program.statements[0],
+                Statement::Expression(Expresion::String(value, _), _) if value == expected
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid_string_escapes() {
+        let tests = vec![
+            (r#""a\qb""#, r"\q", Span::new(2, 4, 1, 3)),
+            (r#""\u{110000}""#, r"\u{110000}", Span::new(1, 11, 1, 2)),
+            (r#""\u{}""#, r"\u{}", Span::new(1, 5, 1, 2)),
+            (r#""\u{12x}""#, r"\u{12x", Span::new(1, 7, 1, 2)),
+            (r#""\u41""#, r"\u", Span::new(1, 3, 1, 2)),
+            ("\"ok\n  \\z\"", r"\z", Span::new(6, 8, 2, 3)),
+        ];
+
+        for (input, sequence, span) in tests {
+            let mut parser = Parser::new(input);
+            parser.parse_program();
+
+            assert_eq!(
+                parser.errors(),
+                vec![ParseError::InvalidEscape {
+                    sequence: sequence.to_string(),
+                    span
+                }],
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_doc_comments_attach_to_let() {
+        let input = "/// The answer.
+///
+///   Indented detail.
+let answer = 42;
+let undocumented = 1;
+/// Dropped, only let bindings take docs.
+answer;
+/// Also dropped.
+let sum = 1 + /// ignored
+  2;";
+
+        let mut parser = Parser::new(input);
+        let program = parser.parse_program();
+
+        assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+
+        let docs: Vec<Option<&str>> = program
+            .statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::Let { doc, .. } => doc.as_deref(),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            docs,
+            vec![
+                Some("The answer.\n\n  Indented detail."),
+                None,
+                None,
+                Some("Also dropped."),
+            ]
+        );
+        assert_eq!(
+            program.statements[0].to_string(),
+            "/// The answer.\n/// \n///   Indented detail.\nlet answer = 42;"
+        );
+        assert_eq!(
+            program.statements[3].to_string(),
+            "/// Also dropped.\nlet sum = (1 + 2);"
+        );
+    }
+
+    #[test]
+    fn test_grouped_expression_span() {
+        let mut parser = Parser::new("(1 + 2) * 3");
+        let program = parser.parse_program();
+
+        let Statement::Expression(Expresion::Infix(left, _, _, span), _) = &program.statements[0]
+        else {
+            panic!("Expected infix expression got {:?}", program.statements[0]);
+        };
+
+        assert_eq!(left.span(), Span::new(0, 7, 1, 1));
+        assert_eq!(*span, Span::new(0, 11, 1, 1));
+    }
+
+    #[test]
+    fn test_mismatched_parentheses() {
+        let tests = vec![
+            (
+                "(1 + 2;",
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::RPAREN,
+                    found: TokenKind::SEMICOLON,
+                    span: Span::new(6, 7, 1, 7),
+                },
+            ),
+            (
+                "((1 + 2)",
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::RPAREN,
+                    found: TokenKind::EOF,
+                    span: Span::new(8, 8, 1, 9),
+                },
+            ),
+            (
+                "(1 + 2))",
+                ParseError::ExpectedExpression {
+                    found: TokenKind::RPAREN,
+                    span: Span::new(7, 8, 1, 8),
+                },
+            ),
+            (
+                "()",
+                ParseError::ExpectedExpression {
+                    found: TokenKind::RPAREN,
+                    span: Span::new(1, 2, 1, 2),
+                },
+            ),
+            (
+                "(1 2)",
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::RPAREN,
+                    found: TokenKind::INT,
+                    span: Span::new(3, 4, 1, 4),
+                },
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(input);
+            parser.parse_program();
+
+            assert_eq!(parser.errors(), vec![expected], "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_if_expression() {
+        let tests = vec![
+            ("if (x < y) { x }", "if (x < y) { x }"),
+            ("if (x < y) { x } else { y }", "if (x < y) { x } else { y }"),
+            (
+                "if (x) { let a = 1; a; } else { return 2; }",
+                "if x { let a = 1; a } else { return 2; }",
+            ),
+            ("if (x) { }", "if x { }"),
+            (
+                "if (a) { 1 } else if (b) { 2 } else { 3 }",
+                "if a { 1 } else { if b { 2 } else { 3 } }",
+            ),
+            (
+                "let max = if (a > b) { a } else { b };",
+                "let max = if (a > b) { a } else { b };",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(input);
+            let program = parser.parse_program();
+
+            assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+            assert_eq!(program.statements.len(), 1, "{}", input);
+            assert_eq!(program.statements[0].to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_if_expression_structure() {
+        let input = "if (a) { 1 } else if (b) { 2 }";
+        let program = Parser::new(input).parse_program();
+
+        let Statement::Expression(
+            Expresion::If {
+                condition,
+                consequence,
+                alternative: Some(alternative),
+                span,
+            },
+            _,
+        ) = &program.statements[0]
+        else {
+            panic!("Expected if expression got {:?}", program.statements[0]);
+        };
+
+        assert!(matches!(**condition, Expresion::Identifer(ref name, _) if name == "a"));
+        assert_eq!(consequence.statements.len(), 1);
+        assert_eq!(consequence.span, Span::new(7, 12, 1, 8));
+        assert_eq!(*span, Span::new(0, 30, 1, 1));
+
+        assert_eq!(alternative.statements.len(), 1);
+        assert!(matches!(
+            &alternative.statements[0],
+            Statement::Expression(
+                Expresion::If {
+                    alternative: None,
+                    ..
+                },
+                _
+            )
+        ));
+    }
+
+    #[test]
+    fn test_if_expression_errors() {
+        let tests = vec![
+            (
+                "if x { 1 }",
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::LPAREN,
+                    found: TokenKind::IDENT,
+                    span: Span::new(3, 4, 1, 4),
+                },
+            ),
+            (
+                "if (x) 1",
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::LBRACE,
+                    found: TokenKind::INT,
+                    span: Span::new(7, 8, 1, 8),
+                },
+            ),
+            (
+                "if (x) { 1 ",
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::RBRACE,
+                    found: TokenKind::EOF,
+                    span: Span::new(11, 11, 1, 12),
+                },
+            ),
+            (
+                "if (x) { 1 } else 2",
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::LBRACE,
+                    found: TokenKind::INT,
+                    span: Span::new(18, 19, 1, 19),
+                },
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(input);
+            parser.parse_program();
+
+            assert_eq!(parser.errors(), vec![expected], "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_block_error_recovery() {
+        let input = "if (x) { let = 1; 2 + ; 3 } else { } 4;";
+
+        let mut parser = Parser::new(input);
+        let program = parser.parse_program();
+
+        assert_eq!(parser.errors().len(), 2, "{:?}", parser.errors());
+        assert_eq!(program.statements.len(), 2);
+        assert_eq!(program.statements[0].to_string(), "if x { 3 } else { }");
+        assert_eq!(program.statements[1].to_string(), "4");
+    }
+
+    #[test]
+    fn test_function_literal() {
+        let tests = vec![
+            ("fn() { }", "fn() { }"),
+            ("fn(x) { x }", "fn(x) { x }"),
+            ("fn(x, y) { x + y; }", "fn(x, y) { (x + y) }"),
+            (
+                "let add = fn(a, b) { return a + b; };",
+                "let add = fn(a, b) { return (a + b); };",
+            ),
+            ("fn(x) { x }(5)", "fn(x) { x }(5)"),
+            ("add()", "add()"),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(input);
+            let program = parser.parse_program();
+
+            assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+            assert_eq!(program.statements.len(), 1, "{}", input);
+            assert_eq!(program.statements[0].to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_function_and_call_structure() {
+        let input = "fn(x, y) { x }(1, 2 + 3)";
+        let program = Parser::new(input).parse_program();
+
+        let Statement::Expression(
+            Expresion::Call {
+                function,
+                args,
+                span,
+            },
+            _,
+        ) = &program.statements[0]
+        else {
+            panic!("Expected call expression got {:?}", program.statements[0]);
+        };
+
+        assert_eq!(*span, Span::new(0, 24, 1, 1));
+        assert_eq!(args.len(), 2);
+        assert!(matches!(args[1], Expresion::Infix(_, Operator::Plus, _, _)));
+
+        let Expresion::Function { params, body, span } = &**function else {
+            panic!("Expected function literal got {:?}", function);
+        };
+
+        assert_eq!(*span, Span::new(0, 14, 1, 1));
+        assert_eq!(body.statements.len(), 1);
+        assert!(matches!(params[0], Expresion::Identifer(ref name, _) if name == "x"));
+        assert!(matches!(params[1], Expresion::Identifer(ref name, _) if name == "y"));
+    }
+
+    #[test]
+    fn test_lexer_sample_program() {
+        let input = "let five = 5;
+        let ten = 10;
+
+        let add = fn(x, y) {
+            x + y;
+        };
+
+        let result = add(five, ten);
+        !-5;
+        5 < 10 > 5;
+
+        if (5 < 10) {
+            return true;
+        } else {
+            return false;
+        }
+
+        10 == 10;
+        10 != 9;";
+
+        let mut parser = Parser::new(input);
+        let program = parser.parse_program();
+
+        assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+        assert_eq!(program.statements.len(), 9);
+        assert_eq!(
+            program.statements[3].to_string(),
+            "let result = add(five, ten);"
+        );
+    }
+
+    #[test]
+    fn test_function_errors() {
+        let tests = vec![
+            (
+                "fn(x, 1) { x }",
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::IDENT,
+                    found: TokenKind::INT,
+                    span: Span::new(6, 7, 1, 7),
+                },
+            ),
+            (
+                "fn(x y) { x }",
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::RPAREN,
+                    found: TokenKind::IDENT,
+                    span: Span::new(5, 6, 1, 6),
+                },
+            ),
+            (
+                "add(1, 2",
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::RPAREN,
+                    found: TokenKind::EOF,
+                    span: Span::new(8, 8, 1, 9),
+                },
+            ),
+            (
+                "add(1,)",
+                ParseError::ExpectedExpression {
+                    found: TokenKind::RPAREN,
+                    span: Span::new(6, 7, 1, 7),
+                },
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(input);
+            parser.parse_program();
+
+            assert_eq!(parser.errors(), vec![expected], "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_array_and_index_expressions() {
+        let tests = vec![
+            ("[]", "[]"),
+            ("[1, 2 * 2, 3 + 3]", "[1, (2 * 2), (3 + 3)]"),
+            (r#"[1, "two", [true]]"#, r#"[1, "two", [true]]"#),
+            ("myArray[1 + 1]", "(myArray[(1 + 1)])"),
+            ("[1, 2][-1]", "([1, 2][(-1)])"),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(input);
+            let program = parser.parse_program();
+
+            assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+            assert_eq!(program.statements.len(), 1, "{}", input);
+            assert_eq!(program.statements[0].to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_index_expression_structure() {
+        let input = "xs[1 + 2]";
+        let program = Parser::new(input).parse_program();
+
+        let Statement::Expression(Expresion::Index { left, index, span }, _) =
+            &program.statements[0]
+        else {
+            panic!("Expected index expression got {:?}", program.statements[0]);
+        };
+
+        assert!(matches!(**left, Expresion::Identifer(ref name, _) if name == "xs"));
+        assert!(matches!(**index, Expresion::Infix(_, Operator::Plus, _, _)));
+        assert_eq!(*span, Span::new(0, 9, 1, 1));
+
+        let program = Parser::new("[1, 2]").parse_program();
+        assert_eq!(program.statements[0].span(), Span::new(0, 6, 1, 1));
+    }
+
+    #[test]
+    fn test_array_errors() {
+        let tests = vec![
+            (
+                "[1, 2",
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::RBRACKET,
+                    found: TokenKind::EOF,
+                    span: Span::new(5, 5, 1, 6),
+                },
+            ),
+            (
+                "a[1",
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::RBRACKET,
+                    found: TokenKind::EOF,
+                    span: Span::new(3, 3, 1, 4),
+                },
+            ),
+            (
+                "a[]",
+                ParseError::ExpectedExpression {
+                    found: TokenKind::RBRACKET,
+                    span: Span::new(2, 3, 1, 3),
+                },
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(input);
+            parser.parse_program();
+
+            assert_eq!(parser.errors(), vec![expected], "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_integer_literal_range() {
+        let program = Parser::new("-9223372036854775808; 9223372036854775807; -5").parse_program();
+
+        assert_eq!(
+            program.statements[0],
+            Statement::Expression(
+                Expresion::Interger(i64::MIN, Span::new(0, 20, 1, 1)),
+                Span::new(0, 20, 1, 1)
+            )
+        );
+        assert!(matches!(
+            program.statements[1],
+            Statement::Expression(Expresion::Interger(i64::MAX, _), _)
+        ));
+        assert_eq!(program.statements[2].to_string(), "(-5)");
+
+        let tests = vec![
+            ("9223372036854775808", Span::new(0, 19, 1, 1)),
+            ("1 - 9223372036854775808", Span::new(4, 23, 1, 5)),
+            ("-9223372036854775809", Span::new(1, 20, 1, 2)),
+        ];
+
+        for (input, span) in tests {
+            let mut parser = Parser::new(input);
+            parser.parse_program();
+
+            assert_eq!(
+                parser.errors(),
+                vec![ParseError::InvalidInteger {
+                    literal: input[span.start..span.end].to_string(),
+                    span,
+                }],
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_comparison_and_arithmetic_precedence() {
+        let tests = vec![
+            ("a <= b == c >= d", "((a <= b) == (c >= d))"),
+            ("a + b % c", "(a + (b % c))"),
+            ("a % b * c", "((a % b) * c)"),
+            ("a ** b ** c", "(a ** (b ** c))"),
+            ("a * b ** c", "(a * (b ** c))"),
+            ("a ** b * c", "((a ** b) * c)"),
+            ("-a ** b", "(-(a ** b))"),
+            ("a ** -b", "(a ** (-b))"),
+            ("a ** f(b)[0]", "(a ** (f(b)[0]))"),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(input);
+            let program = parser.parse_program();
+
+            assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+            assert_eq!(program.statements[0].to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_logical_operator_precedence() {
+        let tests = vec![
+            ("a && b || c", "((a && b) || c)"),
+            ("a || b && c", "(a || (b && c))"),
+            ("a == b && c != d", "((a == b) && (c != d))"),
+            ("a < b || !c", "((a < b) || (!c))"),
+            ("x = a || b", "x = (a || b)"),
+            ("a && b && c", "((a && b) && c)"),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(input);
+            let program = parser.parse_program();
+
+            assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+            assert_eq!(program.statements[0].to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_assignment_expressions() {
+        let tests = vec![
+            ("x = 5;", "x = 5"),
+            ("x = y + 1 * 2", "x = (y + (1 * 2))"),
+            ("a = b = c", "a = b = c"),
+            ("x += 1; ", "x += 1"),
+            ("x -= f(1)", "x -= f(1)"),
+            ("x *= 2 == 4", "x *= (2 == 4)"),
+            ("x /= -y", "x /= (-y)"),
+            ("xs[0] = 1", "(xs[0]) = 1"),
+            (r#"m["a"][i + 1] += 2"#, r#"((m["a"])[(i + 1)]) += 2"#),
+            ("let y = x = 3;", "let y = x = 3;"),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(input);
+            let program = parser.parse_program();
+
+            assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+            assert_eq!(program.statements[0].to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_assignment_structure() {
+        let program = Parser::new("total += 1").parse_program();
+
+        let Statement::Expression(
+            Expresion::Assign {
+                target,
+                operator,
+                value,
+                span,
+            },
+            _,
+        ) = &program.statements[0]
+        else {
+            panic!("Expected assignment got {:?}", program.statements[0]);
+        };
+
+        assert!(matches!(**target, Expresion::Identifer(ref name, _) if name == "total"));
+        assert_eq!(*operator, Some(Operator::Plus));
+        assert!(matches!(**value, Expresion::Interger(1, _)));
+        assert_eq!(*span, Span::new(0, 10, 1, 1));
+    }
+
+    #[test]
+    fn test_assignment_errors() {
+        let tests = vec![
+            ("1 = 2", Span::new(0, 1, 1, 1)),
+            ("f() = 2", Span::new(0, 3, 1, 1)),
+            ("x + 1 = 2", Span::new(0, 5, 1, 1)),
+            ("f()[0] += 2", Span::new(0, 6, 1, 1)),
+        ];
+
+        for (input, span) in tests {
+            let mut parser = Parser::new(input);
+            parser.parse_program();
+
+            assert_eq!(
+                parser.errors()[0],
+                ParseError::InvalidAssignmentTarget { span },
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_loop_statements() {
+        let tests = vec![
+            ("while (x < 3) { x; }", "while (x < 3) { x }"),
+            (
+                "while (true) { if (x) { break; } continue; };",
+                "while true { if x { break; } continue; }",
+            ),
+            (
+                "for (x in [1, 2]) { puts(x) }",
+                "for x in [1, 2] { puts(x) }",
+            ),
+            (
+                "for (k in keys) { while (k) { break } }",
+                "for k in keys { while k { break; } }",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(input);
+            let program = parser.parse_program();
+
+            assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+            assert_eq!(program.statements.len(), 1, "{}", input);
+            assert_eq!(program.statements[0].to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_loop_statement_structure() {
+        let program = Parser::new("for (x in xs) {\n  break;\n}").parse_program();
+
+        let Statement::For {
+            variable,
+            iterable,
+            body,
+            span,
+        } = &program.statements[0]
+        else {
+            panic!("Expected for statement got {:?}", program.statements[0]);
+        };
+
+        assert!(matches!(variable, Expresion::Identifer(name, _) if name == "x"));
+        assert!(matches!(iterable, Expresion::Identifer(name, _) if name == "xs"));
+        assert_eq!(
+            body.statements,
+            vec![Statement::Break(Span::new(18, 23, 2, 3))]
+        );
+        assert_eq!(*span, Span::new(0, 26, 1, 1));
+    }
+
+    #[test]
+    fn test_loop_errors() {
+        let tests = vec![
+            (
+                "break;",
+                ParseError::OutsideLoop {
+                    keyword: TokenKind::BREAK,
+                    span: Span::new(0, 5, 1, 1),
+                },
+            ),
+            (
+                "while (x) { fn() { continue; } }",
+                ParseError::OutsideLoop {
+                    keyword: TokenKind::CONTINUE,
+                    span: Span::new(19, 27, 1, 20),
+                },
+            ),
+            (
+                "for (x of xs) { }",
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::IN,
+                    found: TokenKind::IDENT,
+                    span: Span::new(7, 9, 1, 8),
+                },
+            ),
+            (
+                "for (1 in xs) { }",
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::IDENT,
+                    found: TokenKind::INT,
+                    span: Span::new(5, 6, 1, 6),
+                },
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(input);
+            parser.parse_program();
+
+            assert_eq!(parser.errors()[0], expected, "{}", input);
+        }
+
+        assert_eq!(
+            ParseError::OutsideLoop {
+                keyword: TokenKind::BREAK,
+                span: Span::default(),
+            }
+            .to_string(),
+            "`break` outside of a loop"
+        );
+    }
+
+    #[test]
+    fn test_hash_literals() {
+        let tests = vec![
+            ("{}", "{}"),
+            (
+                r#"{"one": 1, "two": 2, "three": 3}"#,
+                r#"{"one": 1, "two": 2, "three": 3}"#,
+            ),
+            ("{1: true, false: 2,}", "{1: true, false: 2}"),
+            (
+                r#"{"one": 0 + 1, "two": 10 - 8}"#,
+                r#"{"one": (0 + 1), "two": (10 - 8)}"#,
+            ),
+            (
+                r#"let config = {"debug": true}; config["debug"]"#,
+                r#"let config = {"debug": true};"#,
+            ),
+            ("if (x) { {1: 2} }", "if x { {1: 2} }"),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(input);
+            let program = parser.parse_program();
+
+            assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+            assert_eq!(program.statements[0].to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_hash_literal_structure() {
+        let program = Parser::new(r#"{"a": 1, b: c}"#).parse_program();
+
+        let Statement::Expression(Expresion::Hash(pairs, span), _) = &program.statements[0] else {
+            panic!("Expected hash literal got {:?}", program.statements[0]);
+        };
+
+        assert_eq!(*span, Span::new(0, 14, 1, 1));
+        assert_eq!(pairs.len(), 2);
+        assert!(matches!(pairs[0].0, Expresion::String(ref key, _) if key == "a"));
+        assert!(matches!(pairs[1].1, Expresion::Identifer(ref name, _) if name == "c"));
+    }
+
+    #[test]
+    fn test_hash_errors() {
+        let tests = vec![
+            (
+                r#"{"a" 1}"#,
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::COLON,
+                    found: TokenKind::INT,
+                    span: Span::new(5, 6, 1, 6),
+                },
+            ),
+            (
+                r#"{"a": 1 "b": 2}"#,
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::COMMA,
+                    found: TokenKind::STRING,
+                    span: Span::new(8, 11, 1, 9),
+                },
+            ),
+            (
+                r#"{"a": 1"#,
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::COMMA,
+                    found: TokenKind::EOF,
+                    span: Span::new(7, 7, 1, 8),
+                },
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(input);
+            parser.parse_program();
+
+            assert_eq!(parser.errors(), vec![expected], "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+
+        // The statement's expression is the first level, each `(` adds one.
+        let input = nested(MAX_NESTING - 1);
+        let mut parser = Parser::new(&input);
+        parser.parse_program();
+        assert_eq!(parser.errors(), vec![]);
+
+        let tests = vec![
+            nested(MAX_NESTING),
+            nested(100_000),
+            format!("{}1", "1 + ".repeat(200_000)),
+            format!("{}1", "-".repeat(100_000)),
+            format!("{}1{}", "[".repeat(100_000), "]".repeat(100_000)),
+            format!("let f = fn() {{ {} }}", nested(100_000)),
+        ];
+
+        for input in tests {
+            let mut parser = Parser::new(&input);
+            parser.parse_program();
+
+            assert!(
+                matches!(parser.errors(), [ParseError::TooDeeplyNested { .. }]),
+                "{:?}",
+                parser.errors()
+            );
+        }
+
+        // A failed expression does not count against the next statement.
+        let input = format!("{};\n{}", nested(100_000), nested(MAX_NESTING - 1));
+        let mut parser = Parser::new(&input);
+        let program = parser.parse_program();
+
+        assert_eq!(parser.errors().len(), 1);
+        assert_eq!(program.statements.len(), 1);
     }
 }
@@ -1,23 +1,141 @@
-use std::io::Stdin;
+use std::{
+    cell::RefCell,
+    io::{self, BufRead, Write},
+    rc::Rc,
+};
 
-use crate::{lexer::Lexer, token::Token};
+use crate::{
+    ast::Statement, diagnostic, disassembler::disassemble_source, interpreter::Interpreter,
+    parser::Parser,
+};
 
-pub fn start(in_reader: Stdin) {
+const PROMPT: &str = ">> ";
+const DISASM_COMMAND: &str = ":disasm ";
+
+/// Reads one line at a time, evaluates it and prints the result. Bindings
+/// are kept in one environment so later lines can use earlier `let`s.
+/// `:disasm <code>` prints the bytecode for `<code>` instead of running it.
+/// Returns once `input` reaches end of file. `puts` writes to `output`
+/// too.
+pub fn start<R: BufRead, W: Write + 'static>(mut input: R, output: W) -> io::Result<()> {
+    let output = Rc::new(RefCell::new(output));
+    let mut interpreter = Interpreter::with_output(output.clone());
     let mut buffer = String::new();
 
     loop {
+        write!(output.borrow_mut(), "{}", PROMPT)?;
+        output.borrow_mut().flush()?;
+
         buffer.clear();
-        match in_reader.read_line(&mut buffer) {
-            Ok(bytes) => {
-                let mut lexer = Lexer::new(buffer.clone());
-
-                let mut token = lexer.next_token();
-                while token != Token::EOF {
-                    println!("Type: {:?}", token);
-                    token = lexer.next_token();
-                }
+        if input.read_line(&mut buffer)? == 0 {
+            writeln!(output.borrow_mut())?;
+            return Ok(());
+        }
+
+        if let Some(source) = buffer.strip_prefix(DISASM_COMMAND) {
+            match disassemble_source(source) {
+                Ok(listing) => write!(output.borrow_mut(), "{}", listing)?,
+                Err(error) => write!(output.borrow_mut(), "{}", error.render(source))?,
+            }
+
+            continue;
+        }
+
+        let mut parser = Parser::new(&buffer);
+        let program = parser.parse_program();
+
+        if !parser.errors().is_empty() {
+            for error in parser.errors() {
+                write!(
+                    output.borrow_mut(),
+                    "{}",
+                    diagnostic::render(&buffer, error)
+                )?;
             }
-            Err(error) => println!("error {error}"),
+
+            continue;
+        }
+
+        match interpreter.eval_program(&program) {
+            // Blank lines, `let`s and loops evaluate to null, echoing that is
+            // just noise.
+            Ok(_)
+                if matches!(
+                    program.statements.last(),
+                    None | Some(
+                        Statement::Let { .. } | Statement::While { .. } | Statement::For { .. }
+                    )
+                ) => {}
+            Ok(value) => writeln!(output.borrow_mut(), "{}", value)?,
+            Err(error) => writeln!(output.borrow_mut(), "error: {}", error)?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io, io::Write, rc::Rc};
+
+    use super::start;
+
+    /// Lets the test read what `start` wrote once it is done.
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            return self.0.borrow_mut().write(buf);
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            return Ok(());
         }
     }
+
+    fn run(input: &str) -> String {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        start(input.as_bytes(), SharedOutput(output.clone())).unwrap();
+
+        return String::from_utf8(output.take()).unwrap();
+    }
+
+    #[test]
+    fn test_repl_keeps_bindings_between_lines() {
+        let output = run("let a = 5;\nlet double = fn(x) { x * 2 };\ndouble(a)\n");
+
+        assert_eq!(output, ">> >> >> 10\n>> \n");
+
+        let output = run("let n = 0;\nfor (x in [1, 2]) { let n = n + x; }\nn\n");
+
+        assert_eq!(output, ">> >> >> 3\n>> \n");
+    }
+
+    #[test]
+    fn test_repl_reports_errors_and_continues() {
+        let output = run("let = 5;\nfoo\n\"still\" + \" running\"\n");
+
+        assert!(output.contains("error: expected an identifier after `let`"));
+        assert!(output.contains("error: identifier not found: foo"));
+        assert!(output.ends_with(">> still running\n>> \n"));
+    }
+
+    #[test]
+    fn test_repl_disasm_command() {
+        let output = run(":disasm 1 + 2\n:disasm 1 +\n");
+
+        assert!(output.starts_with(">> == main ==\n0000    1 OpConstant 0 (1)\n"));
+        assert!(output.contains("0006    | OpAdd\n"));
+        assert!(output.contains("error: expected an expression, found end of input"));
+    }
+
+    #[test]
+    fn test_repl_puts_writes_to_output() {
+        let output = run("let greet = fn(name) { puts(\"hi \" + name) };\ngreet(\"bob\");\n");
+
+        assert_eq!(output, ">> >> hi bob\nnull\n>> \n");
+    }
+
+    #[test]
+    fn test_repl_exits_on_eof() {
+        assert_eq!(run(""), ">> \n");
+    }
 }
@@ -0,0 +1,44 @@
+/// Location of a token or AST node in the source. `start` and `end` are byte
+/// offsets into the input, `line` and `column` are 1-based and point at `start`.
+/// Columns count chars, not bytes.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Self {
+        return Span {
+            start,
+            end,
+            line,
+            column,
+        };
+    }
+
+    /// Span starting at `self` and ending where `other` ends.
+    pub fn to(self, other: Span) -> Span {
+        return Span {
+            end: other.end,
+            ..self
+        };
+    }
+
+    /// Span of `text[start..end]`, where `text` is the source covered by `self`.
+    pub fn sub_span(self, text: &str, start: usize, end: usize) -> Span {
+        let prefix = &text[..start];
+
+        let (line, column) = match prefix.rfind('\n') {
+            Some(newline) => (
+                self.line + prefix.matches('\n').count(),
+                prefix[newline + 1..].chars().count() + 1,
+            ),
+            None => (self.line, self.column + prefix.chars().count()),
+        };
+
+        return Span::new(self.start + start, self.start + end, line, column);
+    }
+}
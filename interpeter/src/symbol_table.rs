@@ -0,0 +1,345 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::object::Capture;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum SymbolScope {
+    Global,
+    Local,
+    /// A variable of an enclosing function, reached through the closure.
+    Upvalue,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub scope: SymbolScope,
+    pub index: usize,
+}
+
+/// Maps names to the global or local slot the compiler gave them. Each
+/// function body gets its own table enclosed by the one around it.
+#[derive(Default)]
+pub struct SymbolTable {
+    store: HashMap<String, Symbol>,
+    /// Names whose `let` is still being compiled, see `declare`.
+    pending: HashMap<String, Symbol>,
+    /// Names a `let` or `for` somewhere in this function's body binds.
+    /// Functions nested in it see them before their `let` is compiled.
+    pub bound_later: HashSet<String>,
+    pub num_definitions: usize,
+    /// Variables of enclosing functions this function uses, in upvalue
+    /// index order.
+    pub captures: Vec<Capture>,
+    pub outer: Option<Box<SymbolTable>>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        return SymbolTable::default();
+    }
+
+    pub fn new_enclosed(outer: SymbolTable) -> Self {
+        return SymbolTable {
+            outer: Some(Box::new(outer)),
+            ..SymbolTable::default()
+        };
+    }
+
+    /// Gives `name` the next free slot. Defining a name again in the same
+    /// table reuses its slot, like `let` overwrites a binding in the
+    /// evaluator, so a loop body can update what its condition reads.
+    pub fn define(&mut self, name: &str) -> Symbol {
+        let symbol = self.declare(name);
+        self.initialize(name);
+
+        return symbol;
+    }
+
+    /// Like `define`, but a new `name` stays hidden from `resolve` in this
+    /// table until `initialize` is called. The value of a `let` still sees
+    /// what the name meant before, while functions nested in it see the
+    /// new variable, as they only run once the `let` is done.
+    pub fn declare(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.pending.get(name) {
+            return symbol.clone();
+        }
+
+        if let Some(symbol) = self.store.get(name) {
+            if symbol.scope != SymbolScope::Upvalue {
+                return symbol.clone();
+            }
+        }
+
+        let scope = match self.outer {
+            Some(_) => SymbolScope::Local,
+            None => SymbolScope::Global,
+        };
+
+        let symbol = Symbol {
+            name: name.to_owned(),
+            scope,
+            index: self.num_definitions,
+        };
+
+        self.pending.insert(name.to_owned(), symbol.clone());
+        self.num_definitions += 1;
+
+        return symbol;
+    }
+
+    /// Makes a name given out by `declare` visible to `resolve`.
+    pub fn initialize(&mut self, name: &str) {
+        if let Some(symbol) = self.pending.remove(name) {
+            self.store.insert(name.to_owned(), symbol);
+        }
+    }
+
+    /// Gives a global no `let` has defined yet a slot, so a function can
+    /// use a global defined further down. Reading it before its `let` ran
+    /// is an error at runtime, like in the evaluator. Names bound further
+    /// down in an enclosing function resolve to its local instead.
+    pub fn define_forward(&mut self, name: &str) -> Symbol {
+        if let Some(outer) = self.outer.as_mut() {
+            return outer.define_forward(name);
+        }
+
+        if let Some(symbol) = self.pending.get(name) {
+            return symbol.clone();
+        }
+
+        return self.define(name);
+    }
+
+    /// Looks `name` up here, then in the enclosing tables. A variable that
+    /// belongs to an enclosing function is recorded as a capture and
+    /// resolves to an upvalue.
+    pub fn resolve(&mut self, name: &str) -> Option<Symbol> {
+        if let Some(symbol) = self.store.get(name) {
+            return Some(symbol.clone());
+        }
+
+        let symbol = self.outer.as_mut()?.resolve_nested(name)?;
+
+        match symbol.scope {
+            SymbolScope::Global => return Some(symbol),
+            SymbolScope::Local | SymbolScope::Upvalue => return Some(self.capture(symbol)),
+        }
+    }
+
+    /// What `name` means to a function nested in this one, which already
+    /// sees the names still being declared here and those bound further
+    /// down, as it can only run once they are.
+    fn resolve_nested(&mut self, name: &str) -> Option<Symbol> {
+        if let Some(symbol) = self.pending.get(name) {
+            return Some(symbol.clone());
+        }
+
+        if self.bound_later.contains(name) {
+            return Some(self.declare(name));
+        }
+
+        return self.resolve(name);
+    }
+
+    /// Names of the variables defined in this table, indexed by slot.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = vec![String::new(); self.num_definitions];
+
+        for symbol in self.store.values().chain(self.pending.values()) {
+            if symbol.scope != SymbolScope::Upvalue {
+                names[symbol.index].clone_from(&symbol.name);
+            }
+        }
+
+        return names;
+    }
+
+    fn capture(&mut self, outer: Symbol) -> Symbol {
+        let capture = Capture {
+            is_local: outer.scope == SymbolScope::Local,
+            index: outer.index,
+        };
+
+        let index = match self.captures.iter().position(|c| *c == capture) {
+            Some(index) => index,
+            None => {
+                self.captures.push(capture);
+                self.captures.len() - 1
+            }
+        };
+
+        let symbol = Symbol {
+            name: outer.name,
+            scope: SymbolScope::Upvalue,
+            index,
+        };
+
+        self.store.insert(symbol.name.clone(), symbol.clone());
+
+        return symbol;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Symbol, SymbolScope, SymbolTable};
+    use crate::object::Capture;
+
+    fn symbol(name: &str, scope: SymbolScope, index: usize) -> Symbol {
+        return Symbol {
+            name: name.to_owned(),
+            scope,
+            index,
+        };
+    }
+
+    #[test]
+    fn test_define_and_resolve() {
+        let mut global = SymbolTable::new();
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+        assert_eq!(global.define("b"), symbol("b", SymbolScope::Global, 1));
+
+        let mut local = SymbolTable::new_enclosed(global);
+        assert_eq!(local.define("c"), symbol("c", SymbolScope::Local, 0));
+
+        let mut nested = SymbolTable::new_enclosed(local);
+        assert_eq!(nested.define("d"), symbol("d", SymbolScope::Local, 0));
+        assert_eq!(nested.define("d"), symbol("d", SymbolScope::Local, 0));
+        assert_eq!(nested.num_definitions, 1);
+
+        assert_eq!(
+            nested.resolve("a"),
+            Some(symbol("a", SymbolScope::Global, 0))
+        );
+        assert_eq!(
+            nested.resolve("d"),
+            Some(symbol("d", SymbolScope::Local, 0))
+        );
+        assert_eq!(nested.resolve("e"), None);
+    }
+
+    #[test]
+    fn test_declare_hides_name_until_initialized() {
+        let mut global = SymbolTable::new();
+        global.define("x");
+
+        let mut local = SymbolTable::new_enclosed(global);
+        assert_eq!(local.declare("x"), symbol("x", SymbolScope::Local, 0));
+        assert_eq!(
+            local.resolve("x"),
+            Some(symbol("x", SymbolScope::Global, 0))
+        );
+
+        let mut nested = SymbolTable::new_enclosed(local);
+        assert_eq!(
+            nested.resolve("x"),
+            Some(symbol("x", SymbolScope::Upvalue, 0))
+        );
+
+        let mut local = *nested.outer.unwrap();
+        local.initialize("x");
+        assert_eq!(local.resolve("x"), Some(symbol("x", SymbolScope::Local, 0)));
+    }
+
+    #[test]
+    fn test_define_forward() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+
+        let mut local = SymbolTable::new_enclosed(global);
+        assert_eq!(local.resolve("b"), None);
+        assert_eq!(
+            local.define_forward("b"),
+            symbol("b", SymbolScope::Global, 1)
+        );
+        assert_eq!(
+            local.resolve("b"),
+            Some(symbol("b", SymbolScope::Global, 1))
+        );
+
+        let mut global = *local.outer.unwrap();
+        assert_eq!(global.define("b"), symbol("b", SymbolScope::Global, 1));
+        assert_eq!(global.names(), vec![String::from("a"), String::from("b")]);
+    }
+
+    #[test]
+    fn test_resolve_bound_later() {
+        let mut outer = SymbolTable::new_enclosed(SymbolTable::new());
+        outer.define("a");
+        outer.bound_later.insert(String::from("h"));
+
+        let mut inner = SymbolTable::new_enclosed(outer);
+        assert_eq!(
+            inner.resolve("h"),
+            Some(symbol("h", SymbolScope::Upvalue, 0))
+        );
+        assert_eq!(
+            inner.captures,
+            vec![Capture {
+                is_local: true,
+                index: 1
+            }]
+        );
+
+        let mut outer = *inner.outer.unwrap();
+        // Only nested functions see `h` before its `let`.
+        assert_eq!(outer.resolve("h"), None);
+        assert_eq!(outer.define("h"), symbol("h", SymbolScope::Local, 1));
+    }
+
+    #[test]
+    fn test_resolve_captures() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+
+        let mut outer = SymbolTable::new_enclosed(global);
+        outer.define("b");
+        outer.define("c");
+
+        let mut middle = SymbolTable::new_enclosed(outer);
+        middle.define("d");
+
+        let mut inner = SymbolTable::new_enclosed(middle);
+
+        assert_eq!(
+            inner.resolve("a"),
+            Some(symbol("a", SymbolScope::Global, 0))
+        );
+        assert_eq!(
+            inner.resolve("c"),
+            Some(symbol("c", SymbolScope::Upvalue, 0))
+        );
+        assert_eq!(
+            inner.resolve("d"),
+            Some(symbol("d", SymbolScope::Upvalue, 1))
+        );
+        assert_eq!(
+            inner.resolve("c"),
+            Some(symbol("c", SymbolScope::Upvalue, 0))
+        );
+
+        assert_eq!(
+            inner.captures,
+            vec![
+                Capture {
+                    is_local: false,
+                    index: 0,
+                },
+                Capture {
+                    is_local: true,
+                    index: 0,
+                },
+            ]
+        );
+
+        let middle = inner.outer.unwrap();
+        assert_eq!(
+            middle.captures,
+            vec![Capture {
+                is_local: true,
+                index: 1,
+            }]
+        );
+    }
+}
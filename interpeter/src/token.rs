@@ -1,23 +1,25 @@
-impl Token {
+use std::fmt;
+
+use crate::span::Span;
+
+impl TokenKind {
     pub fn from_char(char_literal: char) -> Option<Self> {
         match char_literal {
-            ';' => return Some(Token::SEMICOLON),
-            '(' => return Some(Token::LPAREN),
-            ')' => return Some(Token::RPAREN),
-            '{' => return Some(Token::LBRACE),
-            '}' => return Some(Token::RBRACE),
-            ',' => return Some(Token::COMMA),
-            '+' => return Some(Token::PLUS),
-            '-' => return Some(Token::MINUS),
-            '/' => return Some(Token::SLASH),
-            '*' => return Some(Token::ASTERISK),
-            '<' => return Some(Token::LT),
-            '>' => return Some(Token::GT),
+            ';' => return Some(TokenKind::SEMICOLON),
+            '(' => return Some(TokenKind::LPAREN),
+            ')' => return Some(TokenKind::RPAREN),
+            '{' => return Some(TokenKind::LBRACE),
+            '}' => return Some(TokenKind::RBRACE),
+            '[' => return Some(TokenKind::LBRACKET),
+            ']' => return Some(TokenKind::RBRACKET),
+            ',' => return Some(TokenKind::COMMA),
+            ':' => return Some(TokenKind::COLON),
+            '%' => return Some(TokenKind::PERCENT),
             _ => None,
         }
     }
-    pub fn from_identifier(identifier: String) -> Self {
-        match identifier.as_str() {
+    pub fn from_identifier(identifier: &str) -> Self {
+        match identifier {
             "let" => return Self::LET,
             "fn" => return Self::FUNCTION,
             "true" => return Self::TRUE,
@@ -25,23 +27,34 @@ impl Token {
             "if" => return Self::IF,
             "else" => return Self::ELSE,
             "return" => return Self::RETURN,
-            _ => return Self::IDENT(identifier),
+            "while" => return Self::WHILE,
+            "for" => return Self::FOR,
+            "in" => return Self::IN,
+            "break" => return Self::BREAK,
+            "continue" => return Self::CONTINUE,
+            _ => return Self::IDENT,
         }
     }
+}
 
-    pub fn from_interger_string(interger: String) -> Self {
-        return Token::INT(interger);
-    }
+/// A token borrowing its text from the source it was lexed from.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Token<'src> {
+    pub kind: TokenKind,
+    pub span: Span,
+    pub literal: &'src str,
 }
 
-#[derive(PartialEq, Debug, Clone)]
-pub enum Token {
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum TokenKind {
     ILLEGAL,
     EOF,
 
     // Identifiers + literals
-    IDENT(String), // add, foobar, x, y, ...
-    INT(String),     // 1343456
+    IDENT,      // add, foobar, x, y, ...
+    INT,        // 1343456
+    STRING,     // "foo bar"
+    DOCCOMMENT, // /// Adds two numbers.
 
     // Operators
     ASSIGN,
@@ -50,20 +63,33 @@ pub enum Token {
     BANG,
     ASTERISK,
     SLASH,
+    PERCENT,
+    POWER,
+    PLUSASSIGN,
+    MINUSASSIGN,
+    ASTERISKASSIGN,
+    SLASHASSIGN,
 
     LT,
     GT,
+    LTEQ,
+    GTEQ,
     EQ,
     NOTEQ,
+    AND,
+    OR,
 
     // Delimiters
     COMMA,
     SEMICOLON,
+    COLON,
 
     LPAREN,
     RPAREN,
     LBRACE,
     RBRACE,
+    LBRACKET,
+    RBRACKET,
 
     // Keywords
     FUNCTION,
@@ -73,4 +99,65 @@ pub enum Token {
     IF,
     ELSE,
     RETURN,
+    WHILE,
+    FOR,
+    IN,
+    BREAK,
+    CONTINUE,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let literal = match self {
+            TokenKind::ILLEGAL => "illegal character",
+            TokenKind::EOF => "end of input",
+            TokenKind::IDENT => "identifier",
+            TokenKind::INT => "integer",
+            TokenKind::STRING => "string",
+            TokenKind::DOCCOMMENT => "doc comment",
+            TokenKind::ASSIGN => "=",
+            TokenKind::PLUS => "+",
+            TokenKind::MINUS => "-",
+            TokenKind::BANG => "!",
+            TokenKind::ASTERISK => "*",
+            TokenKind::SLASH => "/",
+            TokenKind::PERCENT => "%",
+            TokenKind::POWER => "**",
+            TokenKind::PLUSASSIGN => "+=",
+            TokenKind::MINUSASSIGN => "-=",
+            TokenKind::ASTERISKASSIGN => "*=",
+            TokenKind::SLASHASSIGN => "/=",
+            TokenKind::LT => "<",
+            TokenKind::GT => ">",
+            TokenKind::LTEQ => "<=",
+            TokenKind::GTEQ => ">=",
+            TokenKind::EQ => "==",
+            TokenKind::NOTEQ => "!=",
+            TokenKind::AND => "&&",
+            TokenKind::OR => "||",
+            TokenKind::COMMA => ",",
+            TokenKind::SEMICOLON => ";",
+            TokenKind::COLON => ":",
+            TokenKind::LPAREN => "(",
+            TokenKind::RPAREN => ")",
+            TokenKind::LBRACE => "{",
+            TokenKind::RBRACE => "}",
+            TokenKind::LBRACKET => "[",
+            TokenKind::RBRACKET => "]",
+            TokenKind::FUNCTION => "fn",
+            TokenKind::LET => "let",
+            TokenKind::TRUE => "true",
+            TokenKind::FALSE => "false",
+            TokenKind::IF => "if",
+            TokenKind::ELSE => "else",
+            TokenKind::RETURN => "return",
+            TokenKind::WHILE => "while",
+            TokenKind::FOR => "for",
+            TokenKind::IN => "in",
+            TokenKind::BREAK => "break",
+            TokenKind::CONTINUE => "continue",
+        };
+
+        write!(f, "{}", literal)
+    }
 }
@@ -0,0 +1,518 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{
+    ast::Operator,
+    builtins,
+    code::{read_u16, Opcode},
+    compiler::Bytecode,
+    evaluator::{
+        eval_assign_index, eval_index, eval_infix, eval_prefix, hash_key, iterate, RuntimeError,
+        MAX_CALL_DEPTH,
+    },
+    object::{Closure, CompiledFunction, Object, Upvalue},
+};
+
+/// A function call in progress. Its arguments and locals live on the
+/// operand stack starting at `base_pointer`.
+struct Frame {
+    closure: Rc<Closure>,
+    ip: usize,
+    base_pointer: usize,
+    /// Stack height when each loop running in this function started,
+    /// indexed by how deeply the loop is nested.
+    loops: Vec<usize>,
+}
+
+/// Runs [`Bytecode`] on an operand stack.
+pub struct Vm {
+    constants: Vec<Object>,
+    stack: Vec<Object>,
+    /// `None` until the `let` of the global has run.
+    globals: Vec<Option<Object>>,
+    global_names: Vec<String>,
+    frames: Vec<Frame>,
+    /// Upvalues still pointing at a live stack slot, so closures created
+    /// in the same frame share them.
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    last_popped: Object,
+}
+
+impl Vm {
+    pub fn new(bytecode: Bytecode) -> Self {
+        let main = CompiledFunction {
+            instructions: bytecode.instructions,
+            lines: bytecode.lines,
+            num_locals: 0,
+            num_params: 0,
+            captures: Vec::new(),
+        };
+
+        let main = Closure {
+            function: Rc::new(main),
+            upvalues: Vec::new(),
+        };
+
+        return Vm {
+            constants: bytecode.constants,
+            stack: Vec::new(),
+            globals: Vec::new(),
+            global_names: bytecode.global_names,
+            frames: vec![Frame {
+                closure: Rc::new(main),
+                ip: 0,
+                base_pointer: 0,
+                loops: Vec::new(),
+            }],
+            open_upvalues: Vec::new(),
+            last_popped: Object::Null,
+        };
+    }
+
+    /// Executes the program and returns the value of its last expression
+    /// statement, like the tree-walking evaluator does.
+    pub fn run(&mut self) -> Result<Object, RuntimeError> {
+        while let Some(op) = self.read_opcode() {
+            match op {
+                Opcode::Constant => {
+                    let index = self.read_u16();
+                    self.push(self.constants[index].clone());
+                }
+                Opcode::Pop => self.last_popped = self.pop(),
+                Opcode::True => self.push(Object::Boolean(true)),
+                Opcode::False => self.push(Object::Boolean(false)),
+                Opcode::Null => self.push(Object::Null),
+                Opcode::Add
+                | Opcode::Sub
+                | Opcode::Mul
+                | Opcode::Div
+                | Opcode::Mod
+                | Opcode::Pow
+                | Opcode::Equal
+                | Opcode::NotEqual
+                | Opcode::GreaterThan
+                | Opcode::LessThan
+                | Opcode::GreaterEqual
+                | Opcode::LessEqual => {
+                    let right = self.pop();
+                    let left = self.pop();
+
+                    self.push(eval_infix(left, infix_operator(op), right)?);
+                }
+                Opcode::Minus => {
+                    let right = self.pop();
+                    self.push(eval_prefix(Operator::Minus, right)?);
+                }
+                Opcode::Bang => {
+                    let right = self.pop();
+                    self.push(eval_prefix(Operator::Not, right)?);
+                }
+                Opcode::Jump => {
+                    let target = self.read_u16();
+                    self.frame().ip = target;
+                }
+                Opcode::JumpNotTruthy => {
+                    let target = self.read_u16();
+
+                    if !self.pop().is_truthy() {
+                        self.frame().ip = target;
+                    }
+                }
+                Opcode::GetGlobal => {
+                    let index = self.read_u16();
+
+                    let Some(Some(value)) = self.globals.get(index).cloned() else {
+                        let name = self.global_names[index].clone();
+                        return Err(RuntimeError::UnknownIdentifier(name));
+                    };
+
+                    self.push(value);
+                }
+                Opcode::SetGlobal => {
+                    let index = self.read_u16();
+
+                    if index >= self.globals.len() {
+                        self.globals.resize(index + 1, None);
+                    }
+
+                    self.globals[index] = Some(self.pop());
+                }
+                Opcode::CheckGlobal => {
+                    let index = self.read_u16();
+
+                    if !matches!(self.globals.get(index), Some(Some(_))) {
+                        let name = self.global_names[index].clone();
+                        return Err(RuntimeError::UndeclaredAssignment(name));
+                    }
+                }
+                Opcode::GetLocal => {
+                    let slot = self.frame().base_pointer + self.read_u8();
+                    self.push(self.stack[slot].clone());
+                }
+                Opcode::SetLocal => {
+                    let slot = self.frame().base_pointer + self.read_u8();
+                    self.stack[slot] = self.pop();
+                }
+                Opcode::Call => {
+                    let argc = self.read_u8();
+                    self.call(argc)?;
+                }
+                Opcode::ReturnValue => {
+                    let value = self.pop();
+
+                    if !self.return_from_frame(value) {
+                        break;
+                    }
+                }
+                Opcode::Closure => {
+                    let index = self.read_u16();
+                    let closure = self.make_closure(index);
+                    self.push(closure);
+                }
+                Opcode::Array => {
+                    let count = self.read_u16();
+                    let elements = self.stack.split_off(self.stack.len() - count);
+                    self.push(Object::Array(Rc::new(elements)));
+                }
+                Opcode::Hash => {
+                    let count = self.read_u16();
+                    let items = self.stack.split_off(self.stack.len() - count);
+
+                    let mut hash = HashMap::with_capacity(count / 2);
+                    let mut items = items.into_iter();
+
+                    while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                        hash.insert(hash_key(&key)?, value);
+                    }
+
+                    self.push(Object::Hash(Rc::new(hash)));
+                }
+                Opcode::Index => {
+                    let index = self.pop();
+                    let left = self.pop();
+                    self.push(eval_index(&left, &index)?);
+                }
+                Opcode::SetIndex => {
+                    let count = self.read_u8();
+                    let operator = match Opcode::try_from(self.read_u8() as u8) {
+                        Ok(Opcode::Null) => None,
+                        Ok(op) => Some(infix_operator(op)),
+                        Err(byte) => unreachable!("OpSetIndex with operator {}", byte),
+                    };
+
+                    let mut base = self.pop();
+                    let value = self.pop();
+                    let indexes = self.stack.split_off(self.stack.len() - count);
+
+                    let value = eval_assign_index(&mut base, &indexes, operator, value)?;
+                    self.push(value);
+                    self.push(base);
+                }
+                Opcode::EnterLoop => {
+                    let depth = self.read_u8();
+                    let height = self.stack.len();
+                    let frame = self.frame();
+
+                    // A `break` out of an enclosing loop may have skipped
+                    // the `ExitLoop` of this one on a previous run.
+                    frame.loops.truncate(depth);
+                    frame.loops.push(height);
+                }
+                Opcode::ExitLoop => {
+                    let depth = self.read_u8();
+                    let frame = self.frame();
+                    let height = frame.loops[depth];
+
+                    frame.loops.truncate(depth);
+                    self.stack.truncate(height);
+                }
+                Opcode::UnwindLoop => {
+                    let depth = self.read_u8();
+                    let kept = self.read_u8();
+                    let height = self.frame().loops[depth] + kept;
+
+                    self.stack.truncate(height);
+                }
+                Opcode::IterInit => {
+                    let iterable = self.pop();
+
+                    self.push(Object::Array(iterate(&iterable)?));
+                    self.push(Object::Integer(0));
+                }
+                Opcode::IterNext => {
+                    let target = self.read_u16();
+                    let top = self.stack.len() - 1;
+
+                    let (Object::Array(items), Object::Integer(position)) =
+                        (&self.stack[top - 1], &self.stack[top])
+                    else {
+                        unreachable!("OpIterNext without OpIterInit");
+                    };
+
+                    match items.get(*position as usize).cloned() {
+                        Some(item) => {
+                            self.stack[top] = Object::Integer(position + 1);
+                            self.push(item);
+                        }
+                        None => self.frame().ip = target,
+                    }
+                }
+                Opcode::GetBuiltin => {
+                    let index = self.read_u8();
+                    self.push(builtins::get(index));
+                }
+                Opcode::GetUpvalue => {
+                    let index = self.read_u8();
+                    let upvalue = Rc::clone(&self.frame().closure.upvalues[index]);
+
+                    let value = match &*upvalue.borrow() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+
+                    self.push(value);
+                }
+                Opcode::SetUpvalue => {
+                    let index = self.read_u8();
+                    let value = self.pop();
+                    let upvalue = Rc::clone(&self.frame().closure.upvalues[index]);
+
+                    let mut upvalue = upvalue.borrow_mut();
+
+                    match &mut *upvalue {
+                        Upvalue::Open(slot) => self.stack[*slot] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                }
+            }
+        }
+
+        return Ok(self.last_popped.clone());
+    }
+
+    /// Calls the function sitting below its `argc` arguments on the stack.
+    fn call(&mut self, argc: usize) -> Result<(), RuntimeError> {
+        let callee_slot = self.stack.len() - 1 - argc;
+
+        match self.stack[callee_slot].clone() {
+            Object::Closure(closure) => {
+                let function = &closure.function;
+
+                if function.num_params != argc {
+                    return Err(RuntimeError::WrongArgumentCount {
+                        expected: function.num_params,
+                        got: argc,
+                    });
+                }
+
+                // The main program has a frame of its own.
+                if self.frames.len() > MAX_CALL_DEPTH {
+                    return Err(RuntimeError::StackOverflow);
+                }
+
+                let base_pointer = callee_slot + 1;
+                let num_locals = function.num_locals;
+
+                self.frames.push(Frame {
+                    closure,
+                    ip: 0,
+                    base_pointer,
+                    loops: Vec::new(),
+                });
+                self.stack.resize(base_pointer + num_locals, Object::Null);
+            }
+            Object::Host(host) => {
+                let args = self.stack.split_off(callee_slot + 1);
+                self.stack.pop();
+
+                self.push((host.func)(&args)?);
+            }
+            callee => return Err(RuntimeError::NotAFunction(callee.type_name())),
+        }
+
+        return Ok(());
+    }
+
+    /// Pops the current frame and leaves `value` in place of the callee.
+    /// Returning from the main program stops the VM, which is signalled by
+    /// returning `false`.
+    fn return_from_frame(&mut self, value: Object) -> bool {
+        if self.frames.len() == 1 {
+            self.last_popped = value;
+            return false;
+        }
+
+        let frame = self.frames.pop().expect("checked above");
+        self.close_upvalues(frame.base_pointer);
+        self.stack.truncate(frame.base_pointer - 1);
+        self.push(value);
+
+        return true;
+    }
+
+    /// Wraps the function constant at `index` in a closure, capturing the
+    /// variables it listed from the current frame.
+    fn make_closure(&mut self, index: usize) -> Object {
+        let Object::CompiledFunction(function) = &self.constants[index] else {
+            unreachable!("OpClosure points at {:?}", self.constants[index]);
+        };
+
+        let function = Rc::clone(function);
+        let frame = self.frame();
+        let base_pointer = frame.base_pointer;
+        let enclosing = Rc::clone(&frame.closure);
+
+        let upvalues = function
+            .captures
+            .iter()
+            .map(|capture| match capture.is_local {
+                true => self.capture_upvalue(base_pointer + capture.index),
+                false => Rc::clone(&enclosing.upvalues[capture.index]),
+            })
+            .collect();
+
+        return Object::Closure(Rc::new(Closure { function, upvalues }));
+    }
+
+    /// Reuses the open upvalue for `slot` if another closure already
+    /// captured it, so both see the same variable.
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| *upvalue.borrow() == Upvalue::Open(slot));
+
+        if let Some(upvalue) = existing {
+            return Rc::clone(upvalue);
+        }
+
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(Rc::clone(&upvalue));
+
+        return upvalue;
+    }
+
+    /// Moves the values of upvalues pointing at `from` or above off the
+    /// stack before those slots are discarded.
+    fn close_upvalues(&mut self, from: usize) {
+        let stack = &self.stack;
+
+        self.open_upvalues.retain(|upvalue| {
+            let Upvalue::Open(slot) = *upvalue.borrow() else {
+                return false;
+            };
+
+            if slot < from {
+                return true;
+            }
+
+            *upvalue.borrow_mut() = Upvalue::Closed(stack[slot].clone());
+
+            return false;
+        });
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        return self.frames.last_mut().expect("main frame is never popped");
+    }
+
+    /// Decodes the next opcode, `None` once the main program has run out.
+    fn read_opcode(&mut self) -> Option<Opcode> {
+        let frame = self.frame();
+        let byte = *frame.closure.function.instructions.get(frame.ip)?;
+        frame.ip += 1;
+
+        return Some(Opcode::try_from(byte).expect("compiler only emits valid opcodes"));
+    }
+
+    fn read_u8(&mut self) -> usize {
+        let frame = self.frame();
+        let operand = frame.closure.function.instructions[frame.ip];
+        frame.ip += 1;
+
+        return operand as usize;
+    }
+
+    fn read_u16(&mut self) -> usize {
+        let frame = self.frame();
+        let operand = read_u16(&frame.closure.function.instructions, frame.ip);
+        frame.ip += 2;
+
+        return operand as usize;
+    }
+
+    fn push(&mut self, object: Object) {
+        self.stack.push(object);
+    }
+
+    fn pop(&mut self) -> Object {
+        return self.stack.pop().expect("compiler keeps the stack balanced");
+    }
+}
+
+fn infix_operator(op: Opcode) -> Operator {
+    match op {
+        Opcode::Add => return Operator::Plus,
+        Opcode::Sub => return Operator::Minus,
+        Opcode::Mul => return Operator::Multiply,
+        Opcode::Div => return Operator::Divide,
+        Opcode::Mod => return Operator::Modulo,
+        Opcode::Pow => return Operator::Power,
+        Opcode::Equal => return Operator::Equals,
+        Opcode::NotEqual => return Operator::NotEquals,
+        Opcode::GreaterThan => return Operator::Gt,
+        Opcode::LessThan => return Operator::Lt,
+        Opcode::GreaterEqual => return Operator::GtEq,
+        Opcode::LessEqual => return Operator::LtEq,
+        _ => unreachable!("{} is not an infix operator", op.name()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Vm;
+    use crate::{
+        compiler::Compiler, corpus, evaluator::RuntimeError, object::Object, parser::Parser,
+    };
+
+    fn run(input: &str) -> String {
+        let mut parser = Parser::new(input);
+        let program = parser.parse_program();
+
+        assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+
+        let bytecode = match Compiler::new().compile(&program) {
+            Ok(bytecode) => bytecode,
+            Err(error) => return format!("error: {}", error),
+        };
+
+        match Vm::new(bytecode).run() {
+            Ok(value) => return value.to_string(),
+            Err(error) => return format!("error: {}", error),
+        }
+    }
+
+    #[test]
+    fn test_vm_corpus() {
+        for (input, expected) in corpus::CASES {
+            assert_eq!(run(input), *expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_vm_stack_overflow() {
+        assert_eq!(
+            run("let f = fn() { f() }; f()"),
+            format!("error: {}", RuntimeError::StackOverflow)
+        );
+    }
+
+    #[test]
+    fn test_vm_locals_do_not_leak_between_calls() {
+        let input = "
+        let f = fn(a) { let b = a * 2; b };
+        let g = fn() { let c = 1; f(c) + f(5) + c };
+        g()";
+
+        assert_eq!(run(input), Object::Integer(13).to_string());
+    }
+}